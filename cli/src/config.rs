@@ -1,12 +1,17 @@
 use serde::Deserialize;
-use std::{collections::HashSet, path::Path};
+use std::{collections::HashSet, error::Error, fmt, path::Path};
 
+use crate::{
+    fanout::{CommandKind, FanoutController},
+    paste::{PasteConfig, PasteController},
+    throttle::{DelayConfig, ThrottledController},
+};
 use plojo_core::{Command, Controller, Machine, Stroke};
-use plojo_input_geminipr::GeminiprMachine;
 use plojo_input_stdin::StdinMachine;
-use plojo_output_wayland::WaylandController;
+use plojo_output_buffer::BufferController;
+use plojo_translator::NumberSeparators;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     #[serde(default)]
     input_machine: InputMachineType,
@@ -21,19 +26,25 @@ pub struct Config {
     #[serde(default)]
     pub space_after: bool,
     #[serde(default)]
-    pub delay_output: bool,
+    delay_output: Option<DelayOutput>,
     #[serde(default)]
     disable_input_strokes: Vec<String>,
     #[serde(default)]
     enable_input_shortcuts: Vec<Vec<String>>,
     #[serde(default)]
     disable_scan_keymap: bool,
+    #[serde(default)]
+    number_separators: Option<NumberSeparators>,
+    #[serde(default)]
+    pub preedit: bool,
+    #[serde(default)]
+    paste: Option<PasteConfig>,
 }
 
 impl Config {
-    /// Creates an input machine from the config. Can panic if failed to create machine.
-    /// Accepts an override to ignore config and use stdin
-    pub fn get_input_machine(&self, use_stdin: bool) -> Box<dyn Machine> {
+    /// Creates an input machine from the config. Fails if the machine was not compiled in or
+    /// could not be created. Accepts an override to ignore config and use stdin
+    pub fn get_input_machine(&self, use_stdin: bool) -> Result<Box<dyn Machine>, BackendError> {
         let input = if use_stdin {
             println!("[INFO] Overriding config to use input from stdin");
             &InputMachineType::Stdin
@@ -42,37 +53,127 @@ impl Config {
         };
         println!("[INFO] Input from: {:?}", input);
         match input {
-            InputMachineType::Stdin => Box::new(StdinMachine::new()) as Box<dyn Machine>,
-            InputMachineType::Geminipr { ref port } => {
-                Box::new(GeminiprMachine::new(port).expect("unable to connect to geminipr machine"))
-                    as Box<dyn Machine>
-            }
-            InputMachineType::Keyboard => unreachable!(),
+            InputMachineType::Stdin => Ok(Box::new(StdinMachine::new()) as Box<dyn Machine>),
+            InputMachineType::Geminipr { ref port } => backends::geminipr(port),
+            InputMachineType::Keyboard => backends::keyboard(self.enable_input_shortcuts.clone()),
         }
     }
 
-    /// Create an output controller from the config
-    /// Accepts an override to ignore config and use stdout
-    pub fn get_output_controller(&self, use_stdout: bool) -> Box<dyn Controller> {
+    /// Create an output controller from the config. Fails if the controller was not compiled in
+    /// or is not supported on this system. Accepts an override to ignore config and use stdout
+    pub fn get_output_controller(
+        &self,
+        use_stdout: bool,
+    ) -> Result<Box<dyn Controller>, BackendError> {
         let output = if use_stdout {
             println!("[INFO] Overriding config to output to stdout");
-            &OutputDispatchType::Stdout
+            OutputDispatchType::Stdout
         } else {
-            &self.output_dispatcher
+            self.output_dispatcher.clone()
         };
         println!("[INFO] Output to: {:?}", output);
+        let delays = self.delay_output.as_ref().and_then(DelayOutput::config);
+        match delays {
+            // the stdout preview is printed for every command, so it is not split into keys
+            Some(delays) if !matches!(output, OutputDispatchType::Stdout) => {
+                println!("[INFO] Delaying output");
+                let config = self.clone();
+                // long text is not split into keys if it is pasted
+                let paste = self.paste.clone().filter(|_| output.has_paste_output());
+                let controller = ThrottledController::spawn(delays, paste, move || {
+                    config.create_controller(&output)
+                })?;
+                Ok(Box::new(controller) as Box<dyn Controller>)
+            }
+            _ => self.create_controller(&output),
+        }
+    }
+
+    /// Whether the output is delayed by the throttled controller
+    fn delays_output(&self) -> bool {
+        self.delay_output
+            .as_ref()
+            .and_then(DelayOutput::config)
+            .is_some()
+    }
+
+    /// Create the output controller, wrapped to paste long text if that is configured and the
+    /// output types into other applications
+    fn create_controller(
+        &self,
+        output: &OutputDispatchType,
+    ) -> Result<Box<dyn Controller>, BackendError> {
+        let controller = self.create_backend(output)?;
+        match self.paste_for(output) {
+            Some(paste) => {
+                println!(
+                    "[INFO] Pasting text with at least {} characters into {:?}",
+                    paste.default_settings().threshold(),
+                    output
+                );
+                let clipboard = backends::clipboard()?;
+                Ok(
+                    Box::new(PasteController::wrap(controller, clipboard, paste.clone()))
+                        as Box<dyn Controller>,
+                )
+            }
+            None => Ok(controller),
+        }
+    }
+
+    /// The paste settings if the output itself is wrapped to paste long text
+    fn paste_for(&self, output: &OutputDispatchType) -> Option<&PasteConfig> {
+        self.paste.as_ref().filter(|_| output.pastes())
+    }
+
+    fn create_backend(
+        &self,
+        output: &OutputDispatchType,
+    ) -> Result<Box<dyn Controller>, BackendError> {
         match output {
             OutputDispatchType::Enigo => {
-                panic!()
-            }
-            OutputDispatchType::MacNative => {
-                panic!()
+                backends::enigo(self.disable_scan_keymap, self.delays_output())
             }
+            OutputDispatchType::MacNative => backends::macos(self.disable_scan_keymap),
             OutputDispatchType::Stdout => {
-                Box::new(StdoutController::new(self.disable_scan_keymap)) as Box<dyn Controller>
+                Ok(Box::new(StdoutController::default()) as Box<dyn Controller>)
             }
-            OutputDispatchType::Wayland => {
-                Box::new(WaylandController::new(self.disable_scan_keymap)) as Box<dyn Controller>
+            OutputDispatchType::Wayland => backends::wayland(self.preedit),
+            OutputDispatchType::X11 => backends::x11(self.disable_scan_keymap),
+            OutputDispatchType::Uinput {
+                ref layout,
+                ref variant,
+            } => backends::uinput(layout, variant),
+            OutputDispatchType::Nvim { ref address } => backends::nvim(address.as_deref()),
+            OutputDispatchType::Tmux {
+                ref target,
+                ref socket_name,
+            } => backends::tmux(target.as_deref(), socket_name.as_deref()),
+            OutputDispatchType::Transcript {
+                ref path,
+                ref captions,
+            } => backends::transcript(path, captions.as_deref()),
+            OutputDispatchType::Broadcast {
+                ref websocket,
+                ref tcp,
+            } => backends::broadcast(websocket.as_deref(), tcp.as_deref()),
+            OutputDispatchType::Fanout { ref outputs } => {
+                let mut fanout = FanoutController::default();
+                for o in outputs {
+                    let name = format!("{:?}", o.output);
+                    // an output that fails to start should not stop the others
+                    match self.create_controller(&o.output) {
+                        Ok(c) => fanout = fanout.with_output(&name, c, o.commands.clone()),
+                        Err(e) => eprintln!("[WARN] Skipping output: {}", e),
+                    }
+                }
+                if fanout.is_empty() {
+                    return Err(BackendError::Failed {
+                        backend: "Fan-out output",
+                        reason: "none of its outputs could be created".to_string(),
+                    });
+                }
+                Ok(Box::new(fanout) as Box<dyn Controller>)
             }
         }
     }
@@ -105,6 +206,11 @@ impl Config {
         self.space_stroke.as_ref().map(|s| Stroke::new(s))
     }
 
+    /// Get the separators for formatting numbers, using the default if they are not configured
+    pub fn get_number_separators(&self) -> NumberSeparators {
+        self.number_separators.clone().unwrap_or_default()
+    }
+
     /// Get the strokes for disabling input (mainly for keyboard input)
     pub fn get_disable_input_strokes(&self) -> HashSet<Stroke> {
         self.disable_input_strokes
@@ -118,7 +224,330 @@ pub fn load(raw_str: &str) -> Result<Config, toml::de::Error> {
     toml::from_str::<Config>(raw_str)
 }
 
-#[derive(Debug, Deserialize)]
+/// Reasons why an input machine or output controller from the config could not be created
+// which variants are constructed depends on the enabled features
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum BackendError {
+    /// The backend was not enabled with its cargo feature when plojo was built
+    NotCompiled {
+        backend: &'static str,
+        feature: &'static str,
+    },
+    /// The backend cannot run on this operating system
+    Unsupported { backend: &'static str },
+    /// The backend is available but failed to start
+    Failed {
+        backend: &'static str,
+        reason: String,
+    },
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotCompiled { backend, feature } => write!(
+                f,
+                "{} was not compiled in. Rebuild with `cargo build --features {}` to use it",
+                backend, feature
+            ),
+            Self::Unsupported { backend } => {
+                write!(f, "{} is not supported on this system", backend)
+            }
+            Self::Failed { backend, reason } => {
+                write!(f, "unable to start {}: {}", backend, reason)
+            }
+        }
+    }
+}
+
+impl Error for BackendError {}
+
+/// Constructors for the backends that are behind cargo features. Each one returns an error
+/// instead if its feature is disabled
+mod backends {
+    use super::BackendError;
+    use crate::paste::Clipboard;
+    use plojo_core::{Controller, Machine};
+
+    #[cfg(feature = "geminipr")]
+    pub(super) fn geminipr(port: &str) -> Result<Box<dyn Machine>, BackendError> {
+        use plojo_input_geminipr::GeminiprMachine;
+        GeminiprMachine::new(port)
+            .map(|m| Box::new(m) as Box<dyn Machine>)
+            .map_err(|e| BackendError::Failed {
+                backend: "Geminipr input",
+                reason: e.to_string(),
+            })
+    }
+
+    #[cfg(not(feature = "geminipr"))]
+    pub(super) fn geminipr(_port: &str) -> Result<Box<dyn Machine>, BackendError> {
+        Err(BackendError::NotCompiled {
+            backend: "Geminipr input",
+            feature: "geminipr",
+        })
+    }
+
+    #[cfg(feature = "keyboard")]
+    pub(super) fn keyboard(
+        reenable_shortcuts: Vec<Vec<String>>,
+    ) -> Result<Box<dyn Machine>, BackendError> {
+        use plojo_input_keyboard::KeyboardMachine;
+        Ok(
+            Box::new(KeyboardMachine::new().with_reenable_shortcuts(reenable_shortcuts))
+                as Box<dyn Machine>,
+        )
+    }
+
+    #[cfg(not(feature = "keyboard"))]
+    pub(super) fn keyboard(
+        _reenable_shortcuts: Vec<Vec<String>>,
+    ) -> Result<Box<dyn Machine>, BackendError> {
+        Err(BackendError::NotCompiled {
+            backend: "Keyboard input",
+            feature: "keyboard",
+        })
+    }
+
+    #[cfg(feature = "enigo")]
+    pub(super) fn enigo(
+        disable_scan_keymap: bool,
+        delayed: bool,
+    ) -> Result<Box<dyn Controller>, BackendError> {
+        use plojo_output_enigo::EnigoController;
+        let mut controller = EnigoController::new(disable_scan_keymap);
+        if delayed {
+            // the delayed output already waits after each key
+            controller = controller.with_typing_delays(0, 0);
+        }
+        Ok(Box::new(controller) as Box<dyn Controller>)
+    }
+
+    #[cfg(not(feature = "enigo"))]
+    pub(super) fn enigo(
+        _disable_scan_keymap: bool,
+        _delayed: bool,
+    ) -> Result<Box<dyn Controller>, BackendError> {
+        Err(BackendError::NotCompiled {
+            backend: "Enigo output",
+            feature: "enigo",
+        })
+    }
+
+    #[cfg(all(feature = "macos", target_os = "macos"))]
+    pub(super) fn macos(disable_scan_keymap: bool) -> Result<Box<dyn Controller>, BackendError> {
+        use plojo_output_macos::MacController;
+        Ok(Box::new(MacController::new(disable_scan_keymap)) as Box<dyn Controller>)
+    }
+
+    #[cfg(all(feature = "macos", not(target_os = "macos")))]
+    pub(super) fn macos(_disable_scan_keymap: bool) -> Result<Box<dyn Controller>, BackendError> {
+        Err(BackendError::Unsupported {
+            backend: "MacNative output",
+        })
+    }
+
+    #[cfg(not(feature = "macos"))]
+    pub(super) fn macos(_disable_scan_keymap: bool) -> Result<Box<dyn Controller>, BackendError> {
+        Err(BackendError::NotCompiled {
+            backend: "MacNative output",
+            feature: "macos",
+        })
+    }
+
+    #[cfg(feature = "wayland")]
+    pub(super) fn wayland(preedit: bool) -> Result<Box<dyn Controller>, BackendError> {
+        use plojo_output_wayland::WaylandController;
+        WaylandController::connect()
+            .map(|c| Box::new(c.with_preedit(preedit)) as Box<dyn Controller>)
+            .map_err(|e| BackendError::Failed {
+                backend: "Wayland output",
+                reason: e.to_string(),
+            })
+    }
+
+    #[cfg(not(feature = "wayland"))]
+    pub(super) fn wayland(_preedit: bool) -> Result<Box<dyn Controller>, BackendError> {
+        Err(BackendError::NotCompiled {
+            backend: "Wayland output",
+            feature: "wayland",
+        })
+    }
+
+    #[cfg(feature = "x11")]
+    pub(super) fn x11(disable_scan_keymap: bool) -> Result<Box<dyn Controller>, BackendError> {
+        use plojo_output_x11::X11Controller;
+        X11Controller::connect(disable_scan_keymap)
+            .map(|c| Box::new(c) as Box<dyn Controller>)
+            .map_err(|e| BackendError::Failed {
+                backend: "X11 output",
+                reason: e.to_string(),
+            })
+    }
+
+    #[cfg(not(feature = "x11"))]
+    pub(super) fn x11(_disable_scan_keymap: bool) -> Result<Box<dyn Controller>, BackendError> {
+        Err(BackendError::NotCompiled {
+            backend: "X11 output",
+            feature: "x11",
+        })
+    }
+
+    #[cfg(feature = "uinput")]
+    pub(super) fn uinput(layout: &str, variant: &str) -> Result<Box<dyn Controller>, BackendError> {
+        use plojo_output_uinput::UinputController;
+        UinputController::create(layout, variant)
+            .map(|c| Box::new(c) as Box<dyn Controller>)
+            .map_err(|e| BackendError::Failed {
+                backend: "uinput output",
+                reason: e.to_string(),
+            })
+    }
+
+    #[cfg(not(feature = "uinput"))]
+    pub(super) fn uinput(
+        _layout: &str,
+        _variant: &str,
+    ) -> Result<Box<dyn Controller>, BackendError> {
+        Err(BackendError::NotCompiled {
+            backend: "uinput output",
+            feature: "uinput",
+        })
+    }
+
+    #[cfg(feature = "nvim")]
+    pub(super) fn nvim(address: Option<&str>) -> Result<Box<dyn Controller>, BackendError> {
+        use plojo_output_nvim::NvimController;
+        match address {
+            Some(address) => NvimController::connect(address),
+            None => NvimController::connect_from_env(),
+        }
+        .map(|c| Box::new(c) as Box<dyn Controller>)
+        .map_err(|e| BackendError::Failed {
+            backend: "Neovim output",
+            reason: e.to_string(),
+        })
+    }
+
+    #[cfg(not(feature = "nvim"))]
+    pub(super) fn nvim(_address: Option<&str>) -> Result<Box<dyn Controller>, BackendError> {
+        Err(BackendError::NotCompiled {
+            backend: "Neovim output",
+            feature: "nvim",
+        })
+    }
+
+    #[cfg(feature = "tmux")]
+    pub(super) fn tmux(
+        target: Option<&str>,
+        socket_name: Option<&str>,
+    ) -> Result<Box<dyn Controller>, BackendError> {
+        use plojo_output_tmux::TmuxController;
+        let mut controller = match target {
+            Some(target) => TmuxController::with_target(target),
+            None => TmuxController::default(),
+        };
+        if let Some(socket_name) = socket_name {
+            controller = controller.with_socket_name(socket_name);
+        }
+        Ok(Box::new(controller) as Box<dyn Controller>)
+    }
+
+    #[cfg(not(feature = "tmux"))]
+    pub(super) fn tmux(
+        _target: Option<&str>,
+        _socket_name: Option<&str>,
+    ) -> Result<Box<dyn Controller>, BackendError> {
+        Err(BackendError::NotCompiled {
+            backend: "tmux output",
+            feature: "tmux",
+        })
+    }
+
+    #[cfg(feature = "transcript")]
+    pub(super) fn transcript(
+        path: &str,
+        captions: Option<&str>,
+    ) -> Result<Box<dyn Controller>, BackendError> {
+        use plojo_output_transcript::TranscriptController;
+        use std::path::Path;
+        let controller = TranscriptController::create(Path::new(path));
+        match captions {
+            Some(captions) => controller.and_then(|c| c.with_captions(Path::new(captions))),
+            None => controller,
+        }
+        .map(|c| Box::new(c) as Box<dyn Controller>)
+        .map_err(|e| BackendError::Failed {
+            backend: "transcript output",
+            reason: e.to_string(),
+        })
+    }
+
+    #[cfg(not(feature = "transcript"))]
+    pub(super) fn transcript(
+        _path: &str,
+        _captions: Option<&str>,
+    ) -> Result<Box<dyn Controller>, BackendError> {
+        Err(BackendError::NotCompiled {
+            backend: "transcript output",
+            feature: "transcript",
+        })
+    }
+
+    #[cfg(feature = "broadcast")]
+    pub(super) fn broadcast(
+        websocket: Option<&str>,
+        tcp: Option<&str>,
+    ) -> Result<Box<dyn Controller>, BackendError> {
+        use plojo_output_broadcast::{
+            BroadcastController, DEFAULT_TCP_ADDRESS, DEFAULT_WEBSOCKET_ADDRESS,
+        };
+        let websocket = websocket.unwrap_or(DEFAULT_WEBSOCKET_ADDRESS);
+        let tcp = tcp.unwrap_or(DEFAULT_TCP_ADDRESS);
+        match BroadcastController::bind(Some(websocket), Some(tcp)) {
+            Ok(controller) => {
+                println!("[INFO] Caption viewer at http://{}/", websocket);
+                Ok(Box::new(controller) as Box<dyn Controller>)
+            }
+            Err(e) => Err(BackendError::Failed {
+                backend: "broadcast output",
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    #[cfg(not(feature = "broadcast"))]
+    pub(super) fn broadcast(
+        _websocket: Option<&str>,
+        _tcp: Option<&str>,
+    ) -> Result<Box<dyn Controller>, BackendError> {
+        Err(BackendError::NotCompiled {
+            backend: "broadcast output",
+            feature: "broadcast",
+        })
+    }
+
+    #[cfg(feature = "paste")]
+    pub(super) fn clipboard() -> Result<Box<dyn Clipboard>, BackendError> {
+        arboard::Clipboard::new()
+            .map(|c| Box::new(c) as Box<dyn Clipboard>)
+            .map_err(|e| BackendError::Failed {
+                backend: "clipboard for pasting",
+                reason: e.to_string(),
+            })
+    }
+
+    #[cfg(not(feature = "paste"))]
+    pub(super) fn clipboard() -> Result<Box<dyn Clipboard>, BackendError> {
+        Err(BackendError::NotCompiled {
+            backend: "clipboard for pasting",
+            feature: "paste",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 enum InputMachineType {
     Stdin,
     Keyboard,
@@ -131,12 +560,86 @@ impl Default for InputMachineType {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 enum OutputDispatchType {
     MacNative,
     Enigo,
     Stdout,
     Wayland,
+    X11,
+    Uinput {
+        layout: String,
+        #[serde(default)]
+        variant: String,
+    },
+    Nvim {
+        #[serde(default)]
+        address: Option<String>,
+    },
+    Tmux {
+        #[serde(default)]
+        target: Option<String>,
+        #[serde(default)]
+        socket_name: Option<String>,
+    },
+    Transcript {
+        path: String,
+        #[serde(default)]
+        captions: Option<String>,
+    },
+    Broadcast {
+        #[serde(default)]
+        websocket: Option<String>,
+        #[serde(default)]
+        tcp: Option<String>,
+    },
+    Fanout {
+        outputs: Vec<FanoutOutput>,
+    },
+}
+
+/// An output of the fan-out controller and the kinds of commands it accepts
+#[derive(Debug, Clone, Deserialize)]
+struct FanoutOutput {
+    output: OutputDispatchType,
+    #[serde(default)]
+    commands: Option<Vec<CommandKind>>,
+}
+
+/// Either whether to delay output with the default delays, or the delays to use
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum DelayOutput {
+    Enabled(bool),
+    Delays(DelayConfig),
+}
+
+impl DelayOutput {
+    fn config(&self) -> Option<DelayConfig> {
+        match self {
+            Self::Enabled(true) => Some(DelayConfig::default()),
+            Self::Enabled(false) => None,
+            Self::Delays(delays) => Some(delays.clone()),
+        }
+    }
+}
+
+impl OutputDispatchType {
+    /// Whether the output types into the focused application with key presses, so that it can
+    /// paste from the clipboard instead. A fan-out output pastes into each of its outputs that can.
+    /// Wayland commits text through the input method, which has to know about all of the text,
+    /// and uinput usually runs without a clipboard
+    fn pastes(&self) -> bool {
+        matches!(self, Self::Enigo | Self::MacNative | Self::X11)
+    }
+
+    /// Whether the output or any of its fan-out outputs pastes
+    fn has_paste_output(&self) -> bool {
+        match self {
+            Self::Fanout { outputs } => outputs.iter().any(|o| o.output.has_paste_output()),
+            output => output.pastes(),
+        }
+    }
 }
 
 impl Default for OutputDispatchType {
@@ -145,12 +648,47 @@ impl Default for OutputDispatchType {
     }
 }
 
-struct StdoutController {}
+/// Prints a preview of the line being written instead of dispatching commands
+#[derive(Default)]
+struct StdoutController {
+    buffer: BufferController,
+}
+
 impl Controller for StdoutController {
-    fn new(_disable_scan_keymap: bool) -> Self {
-        Self {}
-    }
     fn dispatch(&mut self, command: Command) {
-        println!("{:?}", command);
+        self.buffer.dispatch(command);
+        println!("{}", self.buffer.render_line());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paste_config(output: &str) -> Config {
+        load(&format!(
+            "output_dispatcher = {}\n[paste]\nthreshold = 10\n",
+            output
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_paste_outputs() {
+        let config = paste_config(r#""X11""#);
+        assert!(config.paste_for(&config.output_dispatcher).is_some());
+        // Wayland text has to go through the input method, so it is never pasted
+        let config = paste_config(r#""Wayland""#);
+        assert!(config.paste_for(&config.output_dispatcher).is_none());
+        assert!(!config.output_dispatcher.has_paste_output());
+        let config = paste_config(r#"{ Uinput = { layout = "us" } }"#);
+        assert!(config.paste_for(&config.output_dispatcher).is_none());
+
+        // only the outputs of a fan-out are wrapped
+        let config = paste_config(
+            r#"{ Fanout = { outputs = [{ output = "Wayland" }, { output = "X11" }] } }"#,
+        );
+        assert!(config.paste_for(&config.output_dispatcher).is_none());
+        assert!(config.output_dispatcher.has_paste_output());
     }
 }
@@ -0,0 +1,178 @@
+//! Controller that sends every command to several other controllers
+use plojo_core::{Command, Controller, OutputEvent};
+use serde::Deserialize;
+use std::panic::{self, AssertUnwindSafe};
+
+/// The kinds of commands that an output of the fan-out controller can accept
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum CommandKind {
+    Replace,
+    PrintHello,
+    NoOp,
+    Keys,
+    Raw,
+    Shell,
+    Pending,
+    KeySequence,
+    Wait,
+    Unicode,
+    MoveCursor,
+}
+
+impl CommandKind {
+    /// The kind of a command. Translator commands are never dispatched, so they have no kind
+    fn of(command: &Command) -> Option<Self> {
+        match command {
+            Command::Replace(..) => Some(Self::Replace),
+            Command::PrintHello => Some(Self::PrintHello),
+            Command::NoOp => Some(Self::NoOp),
+            Command::Keys(..) => Some(Self::Keys),
+            Command::Raw(_) => Some(Self::Raw),
+            Command::Shell(..) => Some(Self::Shell),
+            Command::Pending(_) => Some(Self::Pending),
+            Command::KeySequence(_) => Some(Self::KeySequence),
+            Command::Wait(_) => Some(Self::Wait),
+            Command::Unicode(_) => Some(Self::Unicode),
+            Command::MoveCursor(..) => Some(Self::MoveCursor),
+            Command::TranslatorCommand(_) => None,
+        }
+    }
+}
+
+struct Output {
+    name: String,
+    controller: Box<dyn Controller>,
+    // only these kinds of commands are sent to the output, or all of them if there is no filter
+    filter: Option<Vec<CommandKind>>,
+    // set once the output panics so that it is not used again
+    failed: bool,
+}
+
+/// Dispatches each command to all of its outputs. If an output panics, it is disabled and the
+/// other outputs keep working
+#[derive(Default)]
+pub struct FanoutController {
+    outputs: Vec<Output>,
+}
+
+impl FanoutController {
+    /// Add an output that accepts the kinds of commands in the filter (or every command)
+    pub fn with_output(
+        mut self,
+        name: &str,
+        controller: Box<dyn Controller>,
+        filter: Option<Vec<CommandKind>>,
+    ) -> Self {
+        self.outputs.push(Output {
+            name: name.to_string(),
+            controller,
+            filter,
+            failed: false,
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+}
+
+impl Output {
+    fn accepts(&self, command: &Command) -> bool {
+        match (&self.filter, CommandKind::of(command)) {
+            (None, _) => true,
+            (Some(filter), Some(kind)) => filter.contains(&kind),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Run the action on the controller, disabling the output if it panics
+    fn run<T>(&mut self, action: impl FnOnce(&mut dyn Controller) -> T) -> Option<T> {
+        let controller = &mut self.controller;
+        match panic::catch_unwind(AssertUnwindSafe(|| action(controller.as_mut()))) {
+            Ok(result) => Some(result),
+            Err(_) => {
+                eprintln!("[WARN] The {} output failed and was disabled", self.name);
+                self.failed = true;
+                None
+            }
+        }
+    }
+}
+
+impl Controller for FanoutController {
+    fn dispatch(&mut self, command: Command) {
+        for output in self.outputs.iter_mut() {
+            if !output.failed && output.accepts(&command) {
+                let command = command.clone();
+                output.run(|c| c.dispatch(command));
+            }
+        }
+    }
+
+    fn poll_events(&mut self) -> Vec<OutputEvent> {
+        let mut events = vec![];
+        for output in self.outputs.iter_mut().filter(|o| !o.failed) {
+            if let Some(output_events) = output.run(|c| c.poll_events()) {
+                events.extend(output_events);
+            }
+        }
+        events
+    }
+
+    /// The focused application from the first output that knows it
+    fn focused_application(&mut self) -> Option<String> {
+        self.outputs
+            .iter_mut()
+            .filter(|o| !o.failed)
+            .find_map(|o| o.run(|c| c.focused_application()).flatten())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_controller::TestController;
+    use std::sync::{Arc, Mutex};
+
+    fn test_output() -> (Box<dyn Controller>, Arc<Mutex<Vec<Command>>>) {
+        let controller = TestController::default();
+        let commands = controller.commands();
+        (Box::new(controller), commands)
+    }
+
+    #[test]
+    fn test_filter() {
+        let (all, all_commands) = test_output();
+        let (text, text_commands) = test_output();
+        let mut fanout = FanoutController::default()
+            .with_output("all", all, None)
+            .with_output("text", text, Some(vec![CommandKind::Replace]));
+
+        let replace = Command::Replace(0, "hi".to_string());
+        fanout.dispatch(replace.clone());
+        fanout.dispatch(Command::Raw(4));
+        assert_eq!(
+            *all_commands.lock().unwrap(),
+            vec![replace.clone(), Command::Raw(4)]
+        );
+        assert_eq!(*text_commands.lock().unwrap(), vec![replace]);
+        assert_eq!(fanout.poll_events().len(), 2);
+    }
+
+    #[test]
+    fn test_failure_isolation() {
+        let (failing, failing_commands) = test_output();
+        let (working, working_commands) = test_output();
+        let mut fanout = FanoutController::default()
+            .with_output("failing", failing, None)
+            .with_output("working", working, Some(vec![CommandKind::NoOp]));
+
+        fanout.dispatch(Command::PrintHello);
+        fanout.dispatch(Command::NoOp);
+        assert_eq!(*failing_commands.lock().unwrap(), vec![]);
+        assert_eq!(*working_commands.lock().unwrap(), vec![Command::NoOp]);
+        // the failed output is no longer polled
+        assert_eq!(fanout.poll_events().len(), 1);
+    }
+}
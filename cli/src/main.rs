@@ -1,10 +1,15 @@
 use clap::{App, Arg, ArgMatches};
-use plojo_core::{Command, Translator};
-use plojo_input_geminipr as geminipr;
+use plojo_core::{Command, OutputEvent, Translator};
 use plojo_translator::StandardTranslator;
-use std::{fs, io, path::Path};
+use std::{fmt::Display, fs, io, path::Path, process};
 
 mod config;
+mod fanout;
+mod paste;
+mod per_app;
+#[cfg(test)]
+mod test_controller;
+mod throttle;
 
 pub fn main() {
     let matches = get_arg_matches();
@@ -13,7 +18,10 @@ pub fn main() {
         // only print ports and exit
         println!("[INFO] Only printing available serial ports");
         println!();
-        geminipr::print_available_ports();
+        #[cfg(feature = "geminipr")]
+        plojo_input_geminipr::print_available_ports();
+        #[cfg(not(feature = "geminipr"))]
+        println!("[INFO] Serial ports are only available with the `geminipr` feature");
         println!();
         println!("[INFO] Exiting.");
         return;
@@ -39,19 +47,28 @@ pub fn main() {
         config.get_space_stroke(),
         config.space_after,
     )
-    .expect("unable to create translator");
+    .expect("unable to create translator")
+    .with_number_separators(config.get_number_separators())
+    .with_pending_output(config.preedit);
     println!("[INFO] Loaded dictionaries");
 
     /* Load machine */
-    let mut machine = config.get_input_machine(matches.is_present("stdin"));
+    let mut machine = config
+        .get_input_machine(matches.is_present("stdin"))
+        .unwrap_or_else(|e| exit_with_error(e));
 
     /* Load controller */
-    let mut controller = config.get_output_controller(matches.is_present("stdout"));
+    let mut controller = config
+        .get_output_controller(matches.is_present("stdout"))
+        .unwrap_or_else(|e| exit_with_error(e));
 
     let disable_input_strokes = config.get_disable_input_strokes();
 
     println!("[INFO] Ready.");
 
+    // whether output is suppressed because a password (or other sensitive) field is focused
+    let mut suppress_output = false;
+
     loop {
         // wait for the next stroke
         let stroke = match machine.read() {
@@ -70,6 +87,31 @@ pub fn main() {
 
         let mut log = String::new();
         log.push_str(&format!("{} ", get_time()));
+
+        // let the translator know about changes in the output before translating
+        for event in controller.poll_events() {
+            match event {
+                OutputEvent::SurroundingText(text) => translator.set_context(&text),
+                OutputEvent::FocusChanged => {
+                    // corrections must not go into the new text field
+                    translator.reset();
+                    log.push_str("(focus changed) ");
+                }
+                OutputEvent::SensitiveField(sensitive) => {
+                    suppress_output = sensitive;
+                    log.push_str(if sensitive {
+                        "(sensitive field, suppressing output) "
+                    } else {
+                        "(resuming output) "
+                    });
+                }
+            }
+        }
+        if suppress_output {
+            // the stroke could be part of a password, so it is neither translated nor logged
+            println!("{}[suppressed]", log);
+            continue;
+        }
         log.push_str(&format!("{:?} => ", stroke));
 
         // translating the stroke
@@ -97,6 +139,12 @@ pub fn main() {
     }
 }
 
+/// Print the error and exit instead of panicking, since it is likely a problem with the config
+fn exit_with_error(e: impl Display) -> ! {
+    eprintln!("[ERROR] {}", e);
+    process::exit(1)
+}
+
 fn get_time() -> String {
     use chrono::prelude::{Local, SecondsFormat};
     let now = Local::now();
@@ -130,7 +178,7 @@ fn get_arg_matches() -> ArgMatches<'static> {
         .arg(
             Arg::with_name("stdout")
                 .short("o")
-                .help("Overrides the config and prints to stdout instead of dispatching commands"),
+                .help("Overrides the config and prints a preview of the text to stdout instead of dispatching commands"),
         )
         .get_matches()
 }
@@ -0,0 +1,202 @@
+//! Controller that pastes long text from the clipboard instead of typing it one key at a time
+use crate::per_app::PerApp;
+use plojo_core::{Command, Controller, Key, Modifier, OutputEvent};
+use serde::Deserialize;
+use std::{error::Error, thread, time::Duration};
+
+// How long to wait for the application to read the clipboard before restoring it
+const RESTORE_DELAY: u64 = 100;
+
+/// Access to the system clipboard
+pub trait Clipboard {
+    /// The text on the clipboard, if it has text
+    fn get_text(&mut self) -> Option<String>;
+    fn set_text(&mut self, text: &str) -> Result<(), Box<dyn Error>>;
+}
+
+#[cfg(feature = "paste")]
+impl Clipboard for arboard::Clipboard {
+    fn get_text(&mut self) -> Option<String> {
+        arboard::Clipboard::get_text(self).ok()
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        arboard::Clipboard::set_text(self, text)?;
+        Ok(())
+    }
+}
+
+/// When text is pasted and which shortcut pastes it
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PasteSettings {
+    /// Text with at least this many characters is pasted
+    #[serde(default = "default_threshold")]
+    threshold: usize,
+    #[serde(default = "default_key")]
+    key: Key,
+    #[serde(default = "default_modifiers")]
+    modifiers: Vec<Modifier>,
+}
+
+fn default_threshold() -> usize {
+    40
+}
+
+fn default_key() -> Key {
+    Key::Layout('v')
+}
+
+fn default_modifiers() -> Vec<Modifier> {
+    if cfg!(target_os = "macos") {
+        vec![Modifier::Meta]
+    } else {
+        vec![Modifier::Control]
+    }
+}
+
+impl PasteSettings {
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+}
+
+/// Paste settings, with different settings for some applications
+pub type PasteConfig = PerApp<PasteSettings>;
+
+/// Passes commands to another controller, but text that is long enough is put on the clipboard
+/// and pasted. The previous clipboard text is put back afterwards
+pub struct PasteController {
+    controller: Box<dyn Controller>,
+    clipboard: Box<dyn Clipboard>,
+    config: PasteConfig,
+}
+
+impl PasteController {
+    pub fn wrap(
+        controller: Box<dyn Controller>,
+        clipboard: Box<dyn Clipboard>,
+        config: PasteConfig,
+    ) -> Self {
+        Self {
+            controller,
+            clipboard,
+            config,
+        }
+    }
+
+    fn paste(&mut self, backspace_num: usize, text: String, settings: PasteSettings) {
+        let previous = self.clipboard.get_text();
+        if let Err(e) = self.clipboard.set_text(&text) {
+            eprintln!("[WARN] Could not paste text, typing it instead: {}", e);
+            self.controller
+                .dispatch(Command::Replace(backspace_num, text));
+            return;
+        }
+        if backspace_num > 0 {
+            self.controller
+                .dispatch(Command::Replace(backspace_num, String::new()));
+        }
+        self.controller
+            .dispatch(Command::Keys(settings.key, settings.modifiers));
+
+        // applications read the clipboard after they get the key press, so it cannot be restored
+        // right away
+        thread::sleep(Duration::from_millis(RESTORE_DELAY));
+        // only text can be restored, so anything else that was on the clipboard is lost
+        if let Some(previous) = previous {
+            if let Err(e) = self.clipboard.set_text(&previous) {
+                eprintln!("[WARN] Could not restore the clipboard: {}", e);
+            }
+        }
+    }
+}
+
+impl Controller for PasteController {
+    fn dispatch(&mut self, command: Command) {
+        match command {
+            Command::Replace(backspace_num, text) => {
+                let app = self.controller.focused_application();
+                let settings = self.config.for_app(app.as_deref());
+                if text.chars().count() >= settings.threshold {
+                    let settings = settings.clone();
+                    self.paste(backspace_num, text, settings);
+                } else {
+                    self.controller
+                        .dispatch(Command::Replace(backspace_num, text));
+                }
+            }
+            command => self.controller.dispatch(command),
+        }
+    }
+
+    fn poll_events(&mut self) -> Vec<OutputEvent> {
+        self.controller.poll_events()
+    }
+
+    fn focused_application(&mut self) -> Option<String> {
+        self.controller.focused_application()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_controller::{TestClipboard, TestController};
+
+    fn test_paste(app: Option<&str>, command: Command) -> (Vec<Command>, Vec<String>) {
+        let config: PasteConfig = toml::from_str(
+            r#"
+            threshold = 5
+            [apps.XTerm]
+            threshold = 3
+            key = { Layout = "V" }
+            modifiers = ["Control", "Shift"]
+            "#,
+        )
+        .unwrap();
+        let controller = TestController::default().with_app(app);
+        let commands = controller.commands();
+        let clipboard = TestClipboard::with_text("old");
+        let history = clipboard.history();
+        let mut paste = PasteController::wrap(Box::new(controller), Box::new(clipboard), config);
+        paste.dispatch(command);
+        let commands = commands.lock().unwrap().clone();
+        let history = history.lock().unwrap().clone();
+        (commands, history)
+    }
+
+    #[test]
+    fn test_short_text() {
+        let command = Command::Replace(1, "word".to_string());
+        let (commands, history) = test_paste(None, command.clone());
+        assert_eq!(commands, vec![command]);
+        assert_eq!(history, vec!["old"]);
+    }
+
+    #[test]
+    fn test_paste_text() {
+        let (commands, history) = test_paste(None, Command::Replace(2, "longer".to_string()));
+        assert_eq!(
+            commands,
+            vec![
+                Command::Replace(2, "".to_string()),
+                Command::Keys(Key::Layout('v'), default_modifiers()),
+            ]
+        );
+        assert_eq!(history, vec!["old", "longer", "old"]);
+    }
+
+    #[test]
+    fn test_app_settings() {
+        let (commands, history) =
+            test_paste(Some("xterm"), Command::Replace(0, "ls\n".to_string()));
+        assert_eq!(
+            commands,
+            vec![Command::Keys(
+                Key::Layout('V'),
+                vec![Modifier::Control, Modifier::Shift]
+            )]
+        );
+        assert_eq!(history, vec!["old", "ls\n", "old"]);
+    }
+}
@@ -0,0 +1,63 @@
+//! Settings that can be different for some applications
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Default settings, with different settings for some applications
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(bound = "T: Deserialize<'de>")]
+pub struct PerApp<T> {
+    #[serde(flatten)]
+    default: T,
+    /// Settings for each application by name (the window class on X11). Settings that are left
+    /// out for an application use their defaults, not the settings for other applications
+    #[serde(default)]
+    apps: HashMap<String, T>,
+}
+
+impl<T> PerApp<T> {
+    /// The settings that are used when the application is unknown or has none of its own
+    pub fn default_settings(&self) -> &T {
+        &self.default
+    }
+
+    /// The settings for the application, ignoring case in its name
+    pub fn for_app(&self, app: Option<&str>) -> &T {
+        app.and_then(|app| {
+            self.apps
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(app))
+                .map(|(_, settings)| settings)
+        })
+        .unwrap_or(&self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq, Deserialize)]
+    struct Settings {
+        #[serde(default)]
+        a: u64,
+        #[serde(default)]
+        b: u64,
+    }
+
+    #[test]
+    fn test_for_app() {
+        let settings: PerApp<Settings> = toml::from_str(
+            r#"
+            a = 1
+            b = 2
+            [apps.Firefox]
+            a = 3
+            "#,
+        )
+        .unwrap();
+        assert_eq!(settings.for_app(None), &Settings { a: 1, b: 2 });
+        assert_eq!(settings.for_app(Some("XTerm")), &Settings { a: 1, b: 2 });
+        // the other settings are not taken from the defaults
+        assert_eq!(settings.for_app(Some("firefox")), &Settings { a: 3, b: 0 });
+    }
+}
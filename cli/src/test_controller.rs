@@ -0,0 +1,82 @@
+//! Controller and clipboard for testing the controllers that wrap other controllers
+use crate::paste::Clipboard;
+use plojo_core::{Command, Controller, OutputEvent};
+use std::{
+    error::Error,
+    sync::{Arc, Mutex},
+};
+
+/// Records the commands that it receives, with a fixed focused application. It has the queued
+/// events and a focus change event every time it is polled, and it panics on `PrintHello` so that
+/// it can fail on purpose
+#[derive(Default)]
+pub struct TestController {
+    commands: Arc<Mutex<Vec<Command>>>,
+    events: Arc<Mutex<Vec<OutputEvent>>>,
+    app: Option<String>,
+}
+
+impl TestController {
+    pub fn with_app(mut self, app: Option<&str>) -> Self {
+        self.app = app.map(|a| a.to_string());
+        self
+    }
+
+    /// The commands that the controller received, which can still be read after it is moved
+    pub fn commands(&self) -> Arc<Mutex<Vec<Command>>> {
+        self.commands.clone()
+    }
+
+    /// Events to give the next time the controller is polled, which can be added after it is moved
+    pub fn events(&self) -> Arc<Mutex<Vec<OutputEvent>>> {
+        self.events.clone()
+    }
+}
+
+impl Controller for TestController {
+    fn dispatch(&mut self, command: Command) {
+        if command == Command::PrintHello {
+            panic!("test controller failed");
+        }
+        self.commands.lock().unwrap().push(command);
+    }
+
+    fn poll_events(&mut self) -> Vec<OutputEvent> {
+        let mut events: Vec<_> = self.events.lock().unwrap().drain(..).collect();
+        events.push(OutputEvent::FocusChanged);
+        events
+    }
+
+    fn focused_application(&mut self) -> Option<String> {
+        self.app.clone()
+    }
+}
+
+/// Records every text that is put on the clipboard
+pub struct TestClipboard {
+    history: Arc<Mutex<Vec<String>>>,
+}
+
+impl TestClipboard {
+    pub fn with_text(text: &str) -> Self {
+        Self {
+            history: Arc::new(Mutex::new(vec![text.to_string()])),
+        }
+    }
+
+    /// The texts that were on the clipboard, starting with the first one
+    pub fn history(&self) -> Arc<Mutex<Vec<String>>> {
+        self.history.clone()
+    }
+}
+
+impl Clipboard for TestClipboard {
+    fn get_text(&mut self) -> Option<String> {
+        self.history.lock().unwrap().last().cloned()
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        self.history.lock().unwrap().push(text.to_string());
+        Ok(())
+    }
+}
@@ -0,0 +1,519 @@
+//! Controller that paces the output on another thread, so that slow output never holds up
+//! reading strokes
+use crate::{config::BackendError, paste::PasteConfig, per_app::PerApp};
+use plojo_core::{Command, Controller, OutputEvent};
+use serde::Deserialize;
+use std::{
+    collections::VecDeque,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Condvar, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+// How often the output is checked for events while there is nothing to dispatch
+const EVENT_POLL_INTERVAL: u64 = 50;
+
+/// Delays in milliseconds after each key that is pressed
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DelaySettings {
+    #[serde(default = "default_backspace_delay")]
+    backspace_delay: u64,
+    /// Delay after typing each character
+    #[serde(default = "default_key_delay")]
+    key_delay: u64,
+}
+
+fn default_backspace_delay() -> u64 {
+    5
+}
+
+fn default_key_delay() -> u64 {
+    15
+}
+
+impl Default for DelaySettings {
+    fn default() -> Self {
+        Self {
+            backspace_delay: default_backspace_delay(),
+            key_delay: default_key_delay(),
+        }
+    }
+}
+
+/// Output delays, with different delays for some applications
+pub type DelayConfig = PerApp<DelaySettings>;
+
+/// What a step of the output did, which decides how long to wait after it
+#[derive(Debug, PartialEq)]
+enum Step {
+    Backspace,
+    Character,
+    Other,
+}
+
+#[derive(Default)]
+struct Queue {
+    commands: VecDeque<Command>,
+    // set when the controller is dropped so that the output thread stops once the queue is empty
+    closed: bool,
+    // numbers of the last poll for events that was asked for and of the last one that was done
+    requested_poll: u64,
+    finished_poll: u64,
+}
+
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    changed: Condvar,
+    // notified after the output thread polls the controller for events
+    polled: Condvar,
+    // the focused application the last time the output thread checked
+    app: Mutex<Option<String>>,
+}
+
+/// Queues commands and dispatches them to another controller on its own thread, one key at a
+/// time with a delay after each key. Text that is deleted before it is typed is never typed
+pub struct ThrottledController {
+    shared: Arc<Shared>,
+    events: Receiver<OutputEvent>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl ThrottledController {
+    /// Create the controller on the output thread, since controllers cannot be sent between
+    /// threads. If the controller pastes long text, the paste settings keep that text together so
+    /// that it can still be pasted. Fails if the controller cannot be created
+    pub fn spawn<F>(
+        config: DelayConfig,
+        paste: Option<PasteConfig>,
+        create: F,
+    ) -> Result<Self, BackendError>
+    where
+        F: FnOnce() -> Result<Box<dyn Controller>, BackendError> + Send + 'static,
+    {
+        let shared = Arc::new(Shared::default());
+        let (events_sender, events) = mpsc::channel();
+        let (started_sender, started) = mpsc::channel();
+        let worker_shared = shared.clone();
+        let worker = thread::spawn(move || {
+            let controller = match create() {
+                Ok(controller) => controller,
+                Err(e) => {
+                    started_sender.send(Err(e)).ok();
+                    return;
+                }
+            };
+            started_sender.send(Ok(())).ok();
+            Worker {
+                controller,
+                shared: worker_shared,
+                events: events_sender,
+                config,
+                paste,
+            }
+            .run();
+        });
+        started.recv().unwrap_or_else(|_| {
+            Err(BackendError::Failed {
+                backend: "delayed output",
+                reason: "the output thread stopped".to_string(),
+            })
+        })?;
+
+        Ok(Self {
+            shared,
+            events,
+            worker: Some(worker),
+        })
+    }
+}
+
+impl Controller for ThrottledController {
+    fn dispatch(&mut self, command: Command) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        // key sequences are split so that there is a delay after each key
+        for command in command.simplify() {
+            queue_command(&mut queue.commands, command);
+        }
+        self.shared.changed.notify_one();
+    }
+
+    /// Wait for the output thread to poll the controller, so that events from before the call are
+    /// never missed. This waits for at most one key to be typed
+    fn poll_events(&mut self) -> Vec<OutputEvent> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.requested_poll += 1;
+        let poll = queue.requested_poll;
+        self.shared.changed.notify_one();
+        while queue.finished_poll < poll {
+            let interval = Duration::from_millis(EVENT_POLL_INTERVAL);
+            queue = self.shared.polled.wait_timeout(queue, interval).unwrap().0;
+            if self
+                .worker
+                .as_ref()
+                .is_none_or(|worker| worker.is_finished())
+            {
+                break;
+            }
+        }
+        drop(queue);
+        self.events.try_iter().collect()
+    }
+
+    fn focused_application(&mut self) -> Option<String> {
+        self.shared.app.lock().unwrap().clone()
+    }
+}
+
+impl Drop for ThrottledController {
+    /// Wait for the queued commands to be dispatched so that output is not cut off
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().closed = true;
+        self.shared.changed.notify_one();
+        if let Some(worker) = self.worker.take() {
+            worker.join().ok();
+        }
+    }
+}
+
+struct Worker {
+    controller: Box<dyn Controller>,
+    shared: Arc<Shared>,
+    events: Sender<OutputEvent>,
+    config: DelayConfig,
+    paste: Option<PasteConfig>,
+}
+
+impl Worker {
+    fn run(mut self) {
+        // the settings for the focused application, which can only change between commands
+        let mut settings = None;
+        loop {
+            let (queued, closed) = self.wait_for_commands();
+            if queued {
+                if settings.is_none() {
+                    settings = Some(self.app_settings());
+                }
+                let current = settings.as_ref().unwrap();
+                let step = take_step(
+                    &mut self.shared.queue.lock().unwrap().commands,
+                    current.paste_threshold,
+                );
+                if let Some((command, step)) = step {
+                    self.controller.dispatch(command);
+                    let delay = match step {
+                        Step::Backspace => current.delays.backspace_delay,
+                        Step::Character => current.delays.key_delay,
+                        // a shortcut or other key could change the focused application
+                        Step::Other => {
+                            settings = None;
+                            0
+                        }
+                    };
+                    thread::sleep(Duration::from_millis(delay));
+                }
+            } else if closed {
+                return;
+            } else {
+                // the focus can change while waiting
+                settings = None;
+            }
+            self.poll_events();
+        }
+    }
+
+    /// Pass on the controller's events, answering the polls that were asked for before polling
+    fn poll_events(&mut self) {
+        let poll = self.shared.queue.lock().unwrap().requested_poll;
+        for event in self.controller.poll_events() {
+            self.events.send(event).ok();
+        }
+        self.shared.queue.lock().unwrap().finished_poll = poll;
+        self.shared.polled.notify_all();
+    }
+
+    /// Get the settings for the focused application, and share which application it is
+    fn app_settings(&mut self) -> AppSettings {
+        let app = self.controller.focused_application();
+        let settings = AppSettings {
+            delays: self.config.for_app(app.as_deref()).clone(),
+            paste_threshold: self
+                .paste
+                .as_ref()
+                .map(|paste| paste.for_app(app.as_deref()).threshold()),
+        };
+        *self.shared.app.lock().unwrap() = app;
+        settings
+    }
+
+    /// Wait a bit for commands if the queue is empty. Gives whether there are commands to dispatch
+    /// and whether the controller was dropped
+    fn wait_for_commands(&self) -> (bool, bool) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        if queue.commands.is_empty() && !queue.closed && queue.finished_poll == queue.requested_poll
+        {
+            let interval = Duration::from_millis(EVENT_POLL_INTERVAL);
+            queue = self.shared.changed.wait_timeout(queue, interval).unwrap().0;
+        }
+        (!queue.commands.is_empty(), queue.closed)
+    }
+}
+
+/// The settings that the worker uses for the focused application
+struct AppSettings {
+    delays: DelaySettings,
+    // text with at least this many characters is passed on whole, so that it can be pasted
+    paste_threshold: Option<usize>,
+}
+
+/// Add the command to the queue, merging it with the last command if both replace text so that
+/// text that is deleted right away is never typed
+fn queue_command(commands: &mut VecDeque<Command>, command: Command) {
+    if let (
+        Some(Command::Replace(queued_backspaces, queued_text)),
+        Command::Replace(backspace_num, add_text),
+    ) = (commands.back_mut(), &command)
+    {
+        let queued_len = queued_text.chars().count();
+        if *backspace_num <= queued_len {
+            *queued_text = queued_text
+                .chars()
+                .take(queued_len - backspace_num)
+                .collect();
+        } else {
+            *queued_backspaces += backspace_num - queued_len;
+            queued_text.clear();
+        }
+        queued_text.push_str(add_text);
+        return;
+    }
+    commands.push_back(command);
+}
+
+/// Take a single key press from the front of the queue, or the whole command if it is not text.
+/// Text that is long enough to be pasted is also taken whole, after the backspaces
+fn take_step(
+    commands: &mut VecDeque<Command>,
+    paste_threshold: Option<usize>,
+) -> Option<(Command, Step)> {
+    loop {
+        match commands.front_mut()? {
+            Command::Replace(backspace_num, _) if *backspace_num > 0 => {
+                *backspace_num -= 1;
+                return Some((Command::Replace(1, String::new()), Step::Backspace));
+            }
+            Command::Replace(_, text)
+                if paste_threshold.is_some_and(|threshold| text.chars().count() >= threshold) =>
+            {
+                // pasting presses a shortcut
+                return commands.pop_front().map(|c| (c, Step::Other));
+            }
+            Command::Replace(_, text) if !text.is_empty() => {
+                let c = text.remove(0);
+                if text.is_empty() {
+                    commands.pop_front();
+                }
+                return Some((Command::Replace(0, c.to_string()), Step::Character));
+            }
+            // nothing left to do for this command
+            Command::Replace(..) => {
+                commands.pop_front();
+            }
+            _ => return commands.pop_front().map(|c| (c, Step::Other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        paste::PasteController,
+        test_controller::{TestClipboard, TestController},
+    };
+
+    fn replace(backspace_num: usize, text: &str) -> Command {
+        Command::Replace(backspace_num, text.to_string())
+    }
+
+    fn queue(commands: Vec<Command>) -> VecDeque<Command> {
+        let mut queue = VecDeque::new();
+        for command in commands {
+            queue_command(&mut queue, command);
+        }
+        queue
+    }
+
+    #[test]
+    fn test_coalesce() {
+        assert_eq!(
+            queue(vec![replace(1, " hello"), replace(3, "lp me")]),
+            vec![replace(1, " help me")]
+        );
+        assert_eq!(
+            queue(vec![replace(0, " hi"), replace(5, "")]),
+            vec![replace(2, "")]
+        );
+        // other commands are not merged
+        assert_eq!(
+            queue(vec![replace(0, "a"), Command::Raw(3), replace(1, "")]),
+            vec![replace(0, "a"), Command::Raw(3), replace(1, "")]
+        );
+    }
+
+    #[test]
+    fn test_take_step() {
+        let mut commands = queue(vec![replace(1, "hé"), replace(0, ""), Command::NoOp]);
+        let mut steps = vec![];
+        while let Some(step) = take_step(&mut commands, Some(3)) {
+            steps.push(step);
+        }
+        assert_eq!(
+            steps,
+            vec![
+                (replace(1, ""), Step::Backspace),
+                (replace(0, "h"), Step::Character),
+                (replace(0, "é"), Step::Character),
+                (Command::NoOp, Step::Other),
+            ]
+        );
+
+        // text that is long enough to paste is kept together after the backspaces
+        let mut commands = queue(vec![replace(1, "a"), replace(0, "bc"), replace(0, "d")]);
+        let mut steps = vec![];
+        while let Some(step) = take_step(&mut commands, Some(3)) {
+            steps.push(step);
+        }
+        assert_eq!(
+            steps,
+            vec![
+                (replace(1, ""), Step::Backspace),
+                (replace(0, "abcd"), Step::Other),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_default_delays() {
+        let config: DelayConfig = toml::from_str(
+            r#"
+            key_delay = 30
+            [apps.Firefox]
+            key_delay = 50
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.for_app(None).key_delay, 30);
+        assert_eq!(config.for_app(Some("firefox")).key_delay, 50);
+        assert_eq!(config.for_app(Some("firefox")).backspace_delay, 5);
+        assert_eq!(
+            DelayConfig::default().for_app(None),
+            &DelaySettings::default()
+        );
+    }
+
+    #[test]
+    fn test_throttled_output() {
+        let test_controller = TestController::default();
+        let commands = test_controller.commands();
+        let mut controller = ThrottledController::spawn(DelayConfig::default(), None, move || {
+            Ok(Box::new(test_controller) as Box<dyn Controller>)
+        })
+        .unwrap();
+        controller.dispatch(replace(0, "ab"));
+        controller.dispatch(Command::Unicode(0x63));
+        controller.dispatch(Command::MoveCursor(-1, 0));
+        drop(controller);
+        let left = Command::Keys(
+            plojo_core::Key::Special(plojo_core::SpecialKey::LeftArrow),
+            vec![],
+        );
+        assert_eq!(
+            *commands.lock().unwrap(),
+            vec![replace(0, "a"), replace(0, "b"), replace(0, "c"), left]
+        );
+
+        let failed = ThrottledController::spawn(DelayConfig::default(), None, || {
+            Err(BackendError::Unsupported { backend: "test" })
+        });
+        assert!(failed.is_err());
+    }
+
+    #[test]
+    fn test_throttled_events() {
+        let test_controller = TestController::default();
+        let events = test_controller.events();
+        let commands = test_controller.commands();
+        let mut controller = ThrottledController::spawn(DelayConfig::default(), None, move || {
+            Ok(Box::new(test_controller) as Box<dyn Controller>)
+        })
+        .unwrap();
+        controller.dispatch(replace(0, "long text"));
+        // the output becomes sensitive while text is being typed
+        events
+            .lock()
+            .unwrap()
+            .push(OutputEvent::SensitiveField(true));
+        assert!(controller
+            .poll_events()
+            .contains(&OutputEvent::SensitiveField(true)));
+        controller.dispatch(replace(0, "x"));
+        drop(controller);
+        assert_eq!(commands.lock().unwrap().len(), 10);
+    }
+
+    #[test]
+    fn test_throttled_paste() {
+        let paste: PasteConfig = toml::from_str(
+            r#"
+            threshold = 5
+            key = { Layout = "v" }
+            modifiers = ["Control"]
+            "#,
+        )
+        .unwrap();
+        let test_controller = TestController::default();
+        let commands = test_controller.commands();
+        let clipboard = TestClipboard::with_text("old");
+        let history = clipboard.history();
+        let wrapped_paste = paste.clone();
+        let mut controller =
+            ThrottledController::spawn(DelayConfig::default(), Some(paste), move || {
+                let controller = Box::new(test_controller);
+                Ok(Box::new(PasteController::wrap(
+                    controller,
+                    Box::new(clipboard),
+                    wrapped_paste,
+                )) as Box<dyn Controller>)
+            })
+            .unwrap();
+        controller.dispatch(replace(2, " there, long text"));
+        controller.dispatch(Command::NoOp);
+        controller.dispatch(replace(0, "ok"));
+        drop(controller);
+
+        let paste_key = Command::Keys(
+            plojo_core::Key::Layout('v'),
+            vec![plojo_core::Modifier::Control],
+        );
+        // the backspaces are still pressed one at a time, but the long text is pasted
+        assert_eq!(
+            *commands.lock().unwrap(),
+            vec![
+                replace(1, ""),
+                replace(1, ""),
+                paste_key,
+                Command::NoOp,
+                replace(0, "o"),
+                replace(0, "k"),
+            ]
+        );
+        assert_eq!(
+            *history.lock().unwrap(),
+            vec!["old", " there, long text", "old"]
+        );
+    }
+}
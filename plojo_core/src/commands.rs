@@ -15,6 +15,20 @@ pub enum Command {
     Shell(String, Vec<String>),
     /// Pass a command to the translator to be handled
     TranslatorCommand(String),
+    /// The last few characters of the text that was written may still be replaced by the next
+    /// stroke (ex: when it completes a longer outline). Outputs that type text right away have no
+    /// way to show that it is pending, so they ignore this
+    Pending(usize),
+    /// Press several keys with modifier keys, one after another
+    KeySequence(Vec<(Key, Vec<Modifier>)>),
+    /// Wait for some milliseconds before the next command
+    Wait(u64),
+    /// Type the character with the Unicode code point as text. Outputs that can only press the
+    /// keys of the keyboard layout (uinput) cannot type characters that are not in the layout
+    Unicode(u32),
+    /// Move the cursor right by some characters and down by some lines (negative to move left or
+    /// up)
+    MoveCursor(i32, i32),
 }
 
 #[derive(Debug, Clone, PartialEq, Hash, Eq, Deserialize, Serialize)]
@@ -35,7 +49,19 @@ pub enum SpecialKey {
     F10,
     F11,
     F12,
+    F13,
+    F14,
+    F15,
+    F16,
+    F17,
+    F18,
+    F19,
     F2,
+    F20,
+    F21,
+    F22,
+    F23,
+    F24,
     F3,
     F4,
     F5,
@@ -44,14 +70,43 @@ pub enum SpecialKey {
     F8,
     F9,
     Home,
+    Insert,
     LeftArrow,
+    MediaNextTrack,
+    MediaPlayPause,
+    MediaPreviousTrack,
+    MediaStop,
+    Menu,
+    NumLock,
+    Numpad0,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+    NumpadAdd,
+    NumpadDecimal,
+    NumpadDivide,
+    NumpadEnter,
+    NumpadMultiply,
+    NumpadSubtract,
     PageDown,
     PageUp,
+    Pause,
+    PrintScreen,
     Return,
     RightArrow,
+    ScrollLock,
     Space,
     Tab,
     UpArrow,
+    VolumeDown,
+    VolumeMute,
+    VolumeUp,
 }
 
 #[derive(Debug, Clone, PartialEq, Hash, Eq, Deserialize, Serialize, Copy)]
@@ -71,4 +126,97 @@ impl Command {
     pub fn replace_text(backspace_num: usize, replace_str: &str) -> Self {
         Self::Replace(backspace_num, replace_str.to_owned())
     }
+
+    /// Break a key sequence, Unicode character or cursor movement into key presses and text,
+    /// which every controller can dispatch. Other commands are left as they are, and an invalid
+    /// code point does not type anything
+    pub fn simplify(self) -> Vec<Self> {
+        match self {
+            Self::KeySequence(keys) => keys
+                .into_iter()
+                .map(|(key, modifiers)| Self::Keys(key, modifiers))
+                .collect(),
+            Self::Unicode(code_point) => std::char::from_u32(code_point)
+                .map(|c| Self::add_text(&c.to_string()))
+                .into_iter()
+                .collect(),
+            Self::MoveCursor(right, down) => {
+                let arrow = |key, num: i32| {
+                    std::iter::repeat_n(
+                        Self::Keys(Key::Special(key), vec![]),
+                        num.unsigned_abs() as usize,
+                    )
+                };
+                let vertical = if down < 0 {
+                    SpecialKey::UpArrow
+                } else {
+                    SpecialKey::DownArrow
+                };
+                let horizontal = if right < 0 {
+                    SpecialKey::LeftArrow
+                } else {
+                    SpecialKey::RightArrow
+                };
+                arrow(vertical, down)
+                    .chain(arrow(horizontal, right))
+                    .collect()
+            }
+            command => vec![command],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simplify() {
+        let tab = (Key::Special(SpecialKey::Tab), vec![]);
+        let paste = (Key::Layout('v'), vec![Modifier::Control]);
+        assert_eq!(
+            Command::KeySequence(vec![tab.clone(), paste.clone()]).simplify(),
+            vec![Command::Keys(tab.0, tab.1), Command::Keys(paste.0, paste.1)]
+        );
+        assert_eq!(
+            Command::Unicode(0x2014).simplify(),
+            vec![Command::add_text("—")]
+        );
+        assert_eq!(Command::Unicode(0xD800).simplify(), vec![]);
+        let key = |k| Command::Keys(Key::Special(k), vec![]);
+        assert_eq!(
+            Command::MoveCursor(-2, 1).simplify(),
+            vec![
+                key(SpecialKey::DownArrow),
+                key(SpecialKey::LeftArrow),
+                key(SpecialKey::LeftArrow)
+            ]
+        );
+        assert_eq!(Command::Wait(10).simplify(), vec![Command::Wait(10)]);
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let commands: Vec<Command> = serde_json::from_str(
+            r#"[
+                {"KeySequence": [[{"Special": "Home"}, []], [{"Layout": "a"}, ["Shift"]]]},
+                {"Wait": 100},
+                {"Unicode": 8212},
+                {"MoveCursor": [-3, 0]}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                Command::KeySequence(vec![
+                    (Key::Special(SpecialKey::Home), vec![]),
+                    (Key::Layout('a'), vec![Modifier::Shift])
+                ]),
+                Command::Wait(100),
+                Command::Unicode(8212),
+                Command::MoveCursor(-3, 0),
+            ]
+        );
+    }
 }
@@ -0,0 +1,76 @@
+//! Linux evdev keycodes, for the outputs that send key events to the kernel or the compositor
+use crate::SpecialKey;
+
+/// The evdev keycode of a special key
+pub fn special_key_code(key: &SpecialKey) -> u16 {
+    match key {
+        SpecialKey::Backspace => 14,
+        SpecialKey::CapsLock => 58,
+        SpecialKey::Delete => 111,
+        SpecialKey::DownArrow => 108,
+        SpecialKey::End => 107,
+        SpecialKey::Escape => 1,
+        SpecialKey::F1 => 59,
+        SpecialKey::F2 => 60,
+        SpecialKey::F3 => 61,
+        SpecialKey::F4 => 62,
+        SpecialKey::F5 => 63,
+        SpecialKey::F6 => 64,
+        SpecialKey::F7 => 65,
+        SpecialKey::F8 => 66,
+        SpecialKey::F9 => 67,
+        SpecialKey::F10 => 68,
+        SpecialKey::F11 => 87,
+        SpecialKey::F12 => 88,
+        SpecialKey::F13 => 183,
+        SpecialKey::F14 => 184,
+        SpecialKey::F15 => 185,
+        SpecialKey::F16 => 186,
+        SpecialKey::F17 => 187,
+        SpecialKey::F18 => 188,
+        SpecialKey::F19 => 189,
+        SpecialKey::F20 => 190,
+        SpecialKey::F21 => 191,
+        SpecialKey::F22 => 192,
+        SpecialKey::F23 => 193,
+        SpecialKey::F24 => 194,
+        SpecialKey::Home => 102,
+        SpecialKey::Insert => 110,
+        SpecialKey::LeftArrow => 105,
+        SpecialKey::MediaNextTrack => 163,
+        SpecialKey::MediaPlayPause => 164,
+        SpecialKey::MediaPreviousTrack => 165,
+        SpecialKey::MediaStop => 166,
+        SpecialKey::Menu => 127,
+        SpecialKey::NumLock => 69,
+        SpecialKey::Numpad0 => 82,
+        SpecialKey::Numpad1 => 79,
+        SpecialKey::Numpad2 => 80,
+        SpecialKey::Numpad3 => 81,
+        SpecialKey::Numpad4 => 75,
+        SpecialKey::Numpad5 => 76,
+        SpecialKey::Numpad6 => 77,
+        SpecialKey::Numpad7 => 71,
+        SpecialKey::Numpad8 => 72,
+        SpecialKey::Numpad9 => 73,
+        SpecialKey::NumpadAdd => 78,
+        SpecialKey::NumpadDecimal => 83,
+        SpecialKey::NumpadDivide => 98,
+        SpecialKey::NumpadEnter => 96,
+        SpecialKey::NumpadMultiply => 55,
+        SpecialKey::NumpadSubtract => 74,
+        SpecialKey::PageDown => 109,
+        SpecialKey::PageUp => 104,
+        SpecialKey::Pause => 119,
+        SpecialKey::PrintScreen => 99,
+        SpecialKey::Return => 28,
+        SpecialKey::RightArrow => 106,
+        SpecialKey::ScrollLock => 70,
+        SpecialKey::Space => 57,
+        SpecialKey::Tab => 15,
+        SpecialKey::UpArrow => 103,
+        SpecialKey::VolumeDown => 114,
+        SpecialKey::VolumeMute => 113,
+        SpecialKey::VolumeUp => 115,
+    }
+}
@@ -0,0 +1,11 @@
+/// Something that changed in the output which the translator may need to know about
+#[derive(Debug, Clone, PartialEq, Hash, Eq)]
+pub enum OutputEvent {
+    /// The text before the cursor in the focused text field
+    SurroundingText(String),
+    /// A different text field was focused, so the previous output is no longer in front of the
+    /// cursor
+    FocusChanged,
+    /// Whether the focused text field is for passwords or other sensitive data
+    SensitiveField(bool),
+}
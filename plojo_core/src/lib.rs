@@ -1,12 +1,16 @@
-use std::{error::Error, marker::Sized};
+use std::error::Error;
 
 mod commands;
+#[cfg(feature = "evdev")]
+pub mod evdev_codes;
+mod events;
 mod stroke;
 
 pub use commands::Command;
 pub use commands::Key;
 pub use commands::Modifier;
 pub use commands::SpecialKey;
+pub use events::OutputEvent;
 pub use stroke::RawStroke;
 pub use stroke::Stroke;
 
@@ -15,14 +19,25 @@ pub trait Translator {
     fn translate(&mut self, stroke: Stroke) -> Vec<Command>;
     fn undo(&mut self) -> Vec<Command>;
     fn handle_command(&mut self, command: String);
+    /// Forget everything that was translated before, as if the translator was just created
+    fn reset(&mut self);
+    /// Use the text before the cursor to format the start of the output. Does nothing by default
+    fn set_context(&mut self, _text_before_cursor: &str) {}
 }
 
 /// Controller that can perform a command
 pub trait Controller {
-    fn new(disable_scan_keymap: bool) -> Self
-    where
-        Self: Sized;
     fn dispatch(&mut self, command: Command);
+    /// Get the events that happened in the output since this was last called. Most controllers
+    /// cannot see the output, so there are no events by default
+    fn poll_events(&mut self) -> Vec<OutputEvent> {
+        Vec::new()
+    }
+    /// The name of the application that has focus (ex: the X11 window class). Most controllers
+    /// cannot tell which application it is, so it is unknown by default
+    fn focused_application(&mut self) -> Option<String> {
+        None
+    }
 }
 
 /// A stenography machine (or equivalent)
@@ -0,0 +1,381 @@
+//! Broadcasts every text change to caption viewers over WebSocket and plain TCP.
+//!
+//! Each change is sent as a JSON event with the number of characters deleted, the text inserted
+//! and a snapshot of the current line, so viewers can either apply the changes or just show the
+//! line. TCP clients get one event per line. Opening the WebSocket address in a browser shows a
+//! small caption viewer. Like the transcript output, key presses are applied to a copy of the
+//! document, and a change before the end of the text is sent as rewriting the rest of the text.
+
+use plojo_core::{Command, Controller};
+use plojo_output_buffer::BufferController;
+use serde::Serialize;
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tungstenite::{Message, WebSocket};
+
+/// The address of the WebSocket server if none is given
+pub const DEFAULT_WEBSOCKET_ADDRESS: &str = "127.0.0.1:7100";
+/// The address of the TCP server if none is given
+pub const DEFAULT_TCP_ADDRESS: &str = "127.0.0.1:7101";
+// a client that cannot receive an event in this time is disconnected
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+// how many events can wait to be sent to a client before it is disconnected for falling behind
+const MAX_QUEUED_EVENTS: usize = 1024;
+// the longest HTTP request header that is read to decide if a connection is a WebSocket
+const MAX_REQUEST_HEADER: usize = 8192;
+
+const VIEWER_HTML: &str = include_str!("viewer.html");
+
+/// A change to the text that is sent to every client
+#[derive(Debug, Serialize)]
+struct Event<'a> {
+    deleted: usize,
+    inserted: &'a str,
+    // the whole line that the text ends with after the change
+    line: String,
+}
+
+enum Client {
+    WebSocket(Box<WebSocket<TcpStream>>),
+    Tcp(TcpStream),
+}
+
+impl Client {
+    fn send(&mut self, event: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Client::WebSocket(socket) => socket.send(Message::Text(event.to_string()))?,
+            Client::Tcp(stream) => writeln!(stream, "{}", event)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct State {
+    text: Vec<char>,
+    // the queues of events for each client's writer thread
+    clients: Vec<SyncSender<String>>,
+}
+
+impl State {
+    fn line(&self) -> String {
+        let start = self
+            .text
+            .iter()
+            .rposition(|&c| c == '\n')
+            .map_or(0, |i| i + 1);
+        self.text[start..].iter().collect()
+    }
+
+    /// Queue the event for every client without waiting for it to be sent, dropping the clients
+    /// that have disconnected or fallen too far behind
+    fn broadcast(&mut self, event: &Event) {
+        let event = serde_json::to_string(event).unwrap();
+        self.clients
+            .retain(|client| client.try_send(event.clone()).is_ok());
+    }
+
+    /// Add a client, starting with all the text so far
+    fn add_client(&mut self, client: Client) {
+        let text: String = self.text.iter().collect();
+        let event = Event {
+            deleted: 0,
+            inserted: &text,
+            line: self.line(),
+        };
+        let (sender, receiver) = mpsc::sync_channel(MAX_QUEUED_EVENTS);
+        sender.send(serde_json::to_string(&event).unwrap()).unwrap();
+        // each client is written to on its own thread so that a slow client does not hold up
+        // the others or the output
+        thread::spawn(move || write_events(client, receiver));
+        self.clients.push(sender);
+    }
+}
+
+/// Send the queued events to the client until either side disconnects
+fn write_events(mut client: Client, events: Receiver<String>) {
+    for event in events {
+        if client.send(&event).is_err() {
+            break;
+        }
+    }
+}
+
+pub struct BroadcastController {
+    state: Arc<Mutex<State>>,
+    // the document that the commands edit, which can have its cursor before the end
+    document: BufferController,
+    websocket_address: Option<SocketAddr>,
+    tcp_address: Option<SocketAddr>,
+}
+
+impl BroadcastController {
+    /// Listen for WebSocket and TCP clients on the addresses (ex: "0.0.0.0:7100"). Either server
+    /// can be left out, and a port of 0 picks any free port
+    pub fn bind(websocket_address: Option<&str>, tcp_address: Option<&str>) -> io::Result<Self> {
+        let state = Arc::new(Mutex::new(State::default()));
+        let websocket_address = match websocket_address {
+            Some(address) => Some(listen(address, state.clone(), accept_websocket)?),
+            None => None,
+        };
+        let tcp_address = match tcp_address {
+            Some(address) => Some(listen(address, state.clone(), |s| {
+                Ok(Some(Client::Tcp(s)))
+            })?),
+            None => None,
+        };
+        Ok(Self {
+            state,
+            document: BufferController::default(),
+            websocket_address,
+            tcp_address,
+        })
+    }
+
+    /// The address that the WebSocket server is listening on
+    pub fn websocket_address(&self) -> Option<SocketAddr> {
+        self.websocket_address
+    }
+
+    /// The address that the TCP server is listening on
+    pub fn tcp_address(&self) -> Option<SocketAddr> {
+        self.tcp_address
+    }
+
+    fn replace(&mut self, backspace_num: usize, add_text: &str) {
+        let mut state = self.state.lock().unwrap();
+        let kept = state.text.len().saturating_sub(backspace_num);
+        let deleted = state.text.len() - kept;
+        state.text.truncate(kept);
+        state.text.extend(add_text.chars());
+        let event = Event {
+            deleted,
+            inserted: add_text,
+            line: state.line(),
+        };
+        state.broadcast(&event);
+    }
+}
+
+impl Controller for BroadcastController {
+    fn dispatch(&mut self, command: Command) {
+        // only changes to the text are sent to the viewers
+        if let Some((deleted, added)) = self.document.apply(command) {
+            self.replace(deleted, &added);
+        }
+    }
+}
+
+/// Accept connections on a background thread, adding the clients that `accept` returns
+fn listen(
+    address: &str,
+    state: Arc<Mutex<State>>,
+    accept: fn(TcpStream) -> io::Result<Option<Client>>,
+) -> io::Result<SocketAddr> {
+    let address = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address to listen on"))?;
+    let listener = TcpListener::bind(address)?;
+    let local_address = listener.local_addr()?;
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let state = state.clone();
+            // handshakes are done on their own thread so that a slow client does not stop others
+            // from connecting
+            thread::spawn(move || {
+                let result = stream.and_then(|s| {
+                    s.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                    accept(s)
+                });
+                match result {
+                    Ok(Some(client)) => state.lock().unwrap().add_client(client),
+                    Ok(None) => {}
+                    Err(e) => eprintln!("[WARN] Could not accept caption client: {}", e),
+                }
+            });
+        }
+    });
+    Ok(local_address)
+}
+
+/// Do the WebSocket handshake, or send the viewer page if the request is not for a WebSocket
+fn accept_websocket(stream: TcpStream) -> io::Result<Option<Client>> {
+    stream.set_read_timeout(Some(WRITE_TIMEOUT))?;
+    let header = peek_request_header(&stream)?;
+    if String::from_utf8_lossy(&header)
+        .to_lowercase()
+        .contains("upgrade: websocket")
+    {
+        let socket = tungstenite::accept(stream).map_err(|e| io::Error::other(e.to_string()))?;
+        Ok(Some(Client::WebSocket(Box::new(socket))))
+    } else {
+        let mut stream = stream;
+        // the request has to be read before the connection is closed, or it is reset
+        stream.read_exact(&mut vec![0; header.len()])?;
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            VIEWER_HTML.len(),
+            VIEWER_HTML
+        )?;
+        Ok(None)
+    }
+}
+
+/// Read the HTTP request header without taking it out of the stream, so that it can still be
+/// read for the WebSocket handshake. Fails if the whole header does not arrive in time
+fn peek_request_header(stream: &TcpStream) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0; MAX_REQUEST_HEADER];
+    // peeking returns right away once part of the request has arrived, so it needs its own limit
+    let deadline = Instant::now() + WRITE_TIMEOUT;
+    loop {
+        let len = stream.peek(&mut buf)?;
+        if len == 0 || len == buf.len() || buf[..len].windows(4).any(|w| w == b"\r\n\r\n") {
+            buf.truncate(len);
+            return Ok(buf);
+        }
+        if Instant::now() >= deadline {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "the request header did not arrive in time",
+            ));
+        }
+        // wait for more of the request to arrive
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+
+    fn local_controller() -> BroadcastController {
+        BroadcastController::bind(Some("127.0.0.1:0"), Some("127.0.0.1:0")).unwrap()
+    }
+
+    #[test]
+    fn test_tcp_events() {
+        let mut controller = local_controller();
+        controller.dispatch(Command::Replace(0, "first\nsec".to_string()));
+
+        let stream = TcpStream::connect(controller.tcp_address().unwrap()).unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        // new clients get the text so far
+        assert_eq!(
+            lines.next().unwrap().unwrap(),
+            r#"{"deleted":0,"inserted":"first\nsec","line":"sec"}"#
+        );
+        controller.dispatch(Command::Replace(1, "cond".to_string()));
+        controller.dispatch(Command::Keys(
+            plojo_core::Key::Special(plojo_core::SpecialKey::Return),
+            vec![],
+        ));
+        controller.dispatch(Command::Replace(20, "".to_string()));
+        assert_eq!(
+            lines.next().unwrap().unwrap(),
+            r#"{"deleted":1,"inserted":"cond","line":"second"}"#
+        );
+        assert_eq!(
+            lines.next().unwrap().unwrap(),
+            r#"{"deleted":0,"inserted":"\n","line":""}"#
+        );
+        assert_eq!(
+            lines.next().unwrap().unwrap(),
+            r#"{"deleted":13,"inserted":"","line":""}"#
+        );
+    }
+
+    #[test]
+    fn test_websocket_events() {
+        let mut controller = local_controller();
+        let address = controller.websocket_address().unwrap();
+        let stream = TcpStream::connect(address).unwrap();
+        let (mut socket, _) =
+            tungstenite::client(format!("ws://{}/", address).as_str(), stream).unwrap();
+        assert_eq!(
+            socket.read().unwrap(),
+            Message::Text(r#"{"deleted":0,"inserted":"","line":""}"#.to_string())
+        );
+
+        controller.dispatch(Command::Replace(0, " hello".to_string()));
+        assert_eq!(
+            socket.read().unwrap(),
+            Message::Text(r#"{"deleted":0,"inserted":" hello","line":" hello"}"#.to_string())
+        );
+    }
+
+    #[test]
+    fn test_slow_client() {
+        let mut controller = local_controller();
+        // a client that never reads fills up its socket, which does not hold up the output
+        let _stalled = TcpStream::connect(controller.tcp_address().unwrap()).unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        let start = Instant::now();
+        // each event ends the line, so finding the current line stays quick
+        let text = "x".repeat(4095) + "\n";
+        for _ in 0..2 * MAX_QUEUED_EVENTS {
+            controller.dispatch(Command::Replace(0, text.clone()));
+        }
+        assert!(start.elapsed() < WRITE_TIMEOUT);
+    }
+
+    #[test]
+    fn test_client_falls_behind() {
+        let mut state = State::default();
+        let (sender, _receiver) = mpsc::sync_channel(MAX_QUEUED_EVENTS);
+        state.clients.push(sender);
+        let event = Event {
+            deleted: 0,
+            inserted: "a",
+            line: "a".to_string(),
+        };
+        for _ in 0..MAX_QUEUED_EVENTS {
+            state.broadcast(&event);
+        }
+        assert_eq!(state.clients.len(), 1);
+        // it is dropped once too many events are waiting for it
+        state.broadcast(&event);
+        assert!(state.clients.is_empty());
+    }
+
+    #[test]
+    fn test_viewer_page() {
+        let controller = local_controller();
+        let mut stream = TcpStream::connect(controller.websocket_address().unwrap()).unwrap();
+        write!(stream, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with(VIEWER_HTML));
+    }
+
+    #[test]
+    fn test_partial_request() {
+        let controller = local_controller();
+        let mut stream = TcpStream::connect(controller.websocket_address().unwrap()).unwrap();
+        stream.set_read_timeout(Some(3 * WRITE_TIMEOUT)).unwrap();
+        // a client that never finishes its request is dropped
+        write!(stream, "GET").unwrap();
+        let start = Instant::now();
+        let mut response = vec![];
+        let closed = match stream.read_to_end(&mut response) {
+            Ok(_) => true,
+            Err(e) => e.kind() == io::ErrorKind::ConnectionReset,
+        };
+        assert!(closed);
+        assert!(response.is_empty());
+        assert!(start.elapsed() < 2 * WRITE_TIMEOUT);
+    }
+}
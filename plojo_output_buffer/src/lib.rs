@@ -0,0 +1,360 @@
+//! Applies commands to an in-memory document instead of a real text field.
+//!
+//! This shows what the commands would actually write, which is useful for previewing output,
+//! replaying strokes and testing the translator from end to end.
+
+use plojo_core::{Command, Controller, Key, Modifier, SpecialKey};
+
+/// A document with a cursor that text is typed into
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BufferController {
+    text: Vec<char>,
+    // index of the character after the cursor
+    cursor: usize,
+    // number of characters before the cursor that could still change
+    pending: usize,
+}
+
+impl BufferController {
+    /// Start with some text and the cursor at the end
+    pub fn with_text(text: &str) -> Self {
+        let text: Vec<char> = text.chars().collect();
+        Self {
+            cursor: text.len(),
+            text,
+            pending: 0,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    /// The position of the cursor in characters
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// The text with a `|` to show where the cursor is
+    pub fn render(&self) -> String {
+        let mut rendered: String = self.text[..self.cursor].iter().collect();
+        rendered.push('|');
+        rendered.extend(&self.text[self.cursor..]);
+        rendered
+    }
+
+    /// The line that the cursor is on with a `|` to show where the cursor is. Text that could
+    /// still change is put in brackets
+    pub fn render_line(&self) -> String {
+        let start = self.line_start(self.cursor);
+        let end = self.line_end(self.cursor);
+        let pending_start = self.cursor - self.pending.min(self.cursor - start);
+        let mut rendered: String = self.text[start..pending_start].iter().collect();
+        if pending_start < self.cursor {
+            rendered.push('[');
+            rendered.extend(&self.text[pending_start..self.cursor]);
+            rendered.push(']');
+        }
+        rendered.push('|');
+        rendered.extend(&self.text[self.cursor..end]);
+        rendered
+    }
+
+    /// Dispatch the command and give how the text changed, as the number of characters deleted
+    /// from the end and the text added after that. This is for outputs that can only add to the
+    /// end of their text. Gives None if the text did not change
+    pub fn apply(&mut self, command: Command) -> Option<(usize, String)> {
+        if let Command::Replace(backspace_num, ref add_text) = command {
+            if self.cursor == self.text.len() {
+                // the usual case is quick, without comparing all of the text
+                let deleted = backspace_num.min(self.text.len());
+                let added = add_text.clone();
+                self.dispatch(command);
+                return (deleted > 0 || !added.is_empty()).then_some((deleted, added));
+            }
+        }
+        let before = self.text.clone();
+        self.dispatch(command);
+        let same = before
+            .iter()
+            .zip(&self.text)
+            .take_while(|(a, b)| a == b)
+            .count();
+        if same == before.len() && same == self.text.len() {
+            return None;
+        }
+        Some((before.len() - same, self.text[same..].iter().collect()))
+    }
+
+    fn insert(&mut self, text: &str) {
+        for c in text.chars() {
+            self.text.insert(self.cursor, c);
+            self.cursor += 1;
+        }
+    }
+
+    fn backspace(&mut self, num: usize) {
+        let start = self.cursor.saturating_sub(num);
+        self.text.drain(start..self.cursor);
+        self.cursor = start;
+    }
+
+    fn line_start(&self, pos: usize) -> usize {
+        self.text[..pos]
+            .iter()
+            .rposition(|&c| c == '\n')
+            .map_or(0, |i| i + 1)
+    }
+
+    fn line_end(&self, pos: usize) -> usize {
+        self.text[pos..]
+            .iter()
+            .position(|&c| c == '\n')
+            .map_or(self.text.len(), |i| pos + i)
+    }
+
+    /// Move the cursor to the same column in the previous or next line
+    fn move_line(&mut self, up: bool) {
+        let start = self.line_start(self.cursor);
+        let column = self.cursor - start;
+        let line_start = if up {
+            if start == 0 {
+                self.cursor = 0;
+                return;
+            }
+            self.line_start(start - 1)
+        } else {
+            let end = self.line_end(self.cursor);
+            if end == self.text.len() {
+                self.cursor = end;
+                return;
+            }
+            end + 1
+        };
+        self.cursor = (line_start + column).min(self.line_end(line_start));
+    }
+
+    /// The start of the word before the cursor, skipping any whitespace first
+    fn prev_word_start(&self) -> usize {
+        let mut pos = self.cursor;
+        while pos > 0 && self.text[pos - 1].is_whitespace() {
+            pos -= 1;
+        }
+        while pos > 0 && !self.text[pos - 1].is_whitespace() {
+            pos -= 1;
+        }
+        pos
+    }
+
+    /// The end of the word after the cursor, skipping any whitespace first
+    fn next_word_end(&self) -> usize {
+        let mut pos = self.cursor;
+        while pos < self.text.len() && self.text[pos].is_whitespace() {
+            pos += 1;
+        }
+        while pos < self.text.len() && !self.text[pos].is_whitespace() {
+            pos += 1;
+        }
+        pos
+    }
+
+    /// Apply a key press. Keys that do not edit text or move the cursor are ignored
+    fn press_key(&mut self, key: Key, modifiers: Vec<Modifier>) {
+        let by_word = modifiers == [Modifier::Control] || modifiers == [Modifier::Alt];
+        if !modifiers.is_empty() && modifiers != [Modifier::Shift] && !by_word {
+            // shortcuts are for the application, so they do not change the text
+            return;
+        }
+        match key {
+            // control or alt with a letter is a shortcut
+            Key::Layout(_) if by_word => {}
+            Key::Layout(c) if modifiers.is_empty() => self.insert(&c.to_string()),
+            Key::Layout(c) => self.insert(&c.to_uppercase().to_string()),
+            Key::Special(special_key) => match special_key {
+                SpecialKey::Backspace if by_word => {
+                    let start = self.prev_word_start();
+                    self.backspace(self.cursor - start);
+                }
+                SpecialKey::Backspace => self.backspace(1),
+                SpecialKey::Delete => {
+                    let end = if by_word {
+                        self.next_word_end()
+                    } else {
+                        (self.cursor + 1).min(self.text.len())
+                    };
+                    self.text.drain(self.cursor..end);
+                }
+                SpecialKey::LeftArrow if by_word => self.cursor = self.prev_word_start(),
+                SpecialKey::LeftArrow => self.cursor = self.cursor.saturating_sub(1),
+                SpecialKey::RightArrow if by_word => self.cursor = self.next_word_end(),
+                SpecialKey::RightArrow => self.cursor = (self.cursor + 1).min(self.text.len()),
+                SpecialKey::UpArrow => self.move_line(true),
+                SpecialKey::DownArrow => self.move_line(false),
+                SpecialKey::Home => self.cursor = self.line_start(self.cursor),
+                SpecialKey::End => self.cursor = self.line_end(self.cursor),
+                SpecialKey::PageUp => self.cursor = 0,
+                SpecialKey::PageDown => self.cursor = self.text.len(),
+                SpecialKey::Return | SpecialKey::NumpadEnter => self.insert("\n"),
+                SpecialKey::Space => self.insert(" "),
+                SpecialKey::Tab => self.insert("\t"),
+                special_key => {
+                    if let Some(c) = numpad_char(&special_key) {
+                        self.insert(&c.to_string());
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// The character that a numpad key types, as if num lock is on
+fn numpad_char(key: &SpecialKey) -> Option<char> {
+    let c = match key {
+        SpecialKey::Numpad0 => '0',
+        SpecialKey::Numpad1 => '1',
+        SpecialKey::Numpad2 => '2',
+        SpecialKey::Numpad3 => '3',
+        SpecialKey::Numpad4 => '4',
+        SpecialKey::Numpad5 => '5',
+        SpecialKey::Numpad6 => '6',
+        SpecialKey::Numpad7 => '7',
+        SpecialKey::Numpad8 => '8',
+        SpecialKey::Numpad9 => '9',
+        SpecialKey::NumpadAdd => '+',
+        SpecialKey::NumpadDecimal => '.',
+        SpecialKey::NumpadDivide => '/',
+        SpecialKey::NumpadMultiply => '*',
+        SpecialKey::NumpadSubtract => '-',
+        _ => return None,
+    };
+    Some(c)
+}
+
+impl Controller for BufferController {
+    fn dispatch(&mut self, command: Command) {
+        // anything other than new pending text makes the text final
+        self.pending = 0;
+        match command {
+            Command::Replace(backspace_num, add_text) => {
+                self.backspace(backspace_num);
+                self.insert(&add_text);
+            }
+            Command::Keys(key, modifiers) => self.press_key(key, modifiers),
+            Command::Pending(num) => self.pending = num,
+            command @ (Command::KeySequence(_) | Command::Unicode(_) | Command::MoveCursor(..)) => {
+                for command in command.simplify() {
+                    self.dispatch(command);
+                }
+            }
+            // the document cannot show anything for these, and does not need to wait
+            Command::PrintHello
+            | Command::NoOp
+            | Command::Raw(_)
+            | Command::Shell(..)
+            | Command::Wait(_) => {}
+            Command::TranslatorCommand(_) => panic!("cannot handle translator command"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(buffer: &mut BufferController, key: SpecialKey, modifiers: Vec<Modifier>) {
+        buffer.dispatch(Command::Keys(Key::Special(key), modifiers));
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut buffer = BufferController::default();
+        buffer.dispatch(Command::Replace(0, "Hello wörld".to_string()));
+        buffer.dispatch(Command::Replace(4, "orld!".to_string()));
+        assert_eq!(buffer.text(), "Hello world!");
+        assert_eq!(buffer.cursor(), 12);
+        // backspaces past the start are ignored
+        buffer.dispatch(Command::Replace(20, "a".to_string()));
+        assert_eq!(buffer.render(), "a|");
+    }
+
+    #[test]
+    fn test_cursor_keys() {
+        let mut buffer = BufferController::with_text("one two\nthree");
+        keys(&mut buffer, SpecialKey::UpArrow, vec![]);
+        assert_eq!(buffer.render(), "one t|wo\nthree");
+        keys(&mut buffer, SpecialKey::LeftArrow, vec![Modifier::Control]);
+        buffer.dispatch(Command::Replace(1, ", ".to_string()));
+        assert_eq!(buffer.render(), "one, |two\nthree");
+        keys(&mut buffer, SpecialKey::End, vec![]);
+        keys(&mut buffer, SpecialKey::DownArrow, vec![]);
+        assert_eq!(buffer.render(), "one, two\nthree|");
+        keys(&mut buffer, SpecialKey::Home, vec![]);
+        keys(&mut buffer, SpecialKey::Delete, vec![]);
+        assert_eq!(buffer.render(), "one, two\n|hree");
+    }
+
+    #[test]
+    fn test_editing_keys() {
+        let mut buffer = BufferController::with_text("some words");
+        keys(&mut buffer, SpecialKey::Backspace, vec![Modifier::Control]);
+        assert_eq!(buffer.render(), "some |");
+        keys(&mut buffer, SpecialKey::Return, vec![]);
+        buffer.dispatch(Command::Keys(Key::Layout('a'), vec![Modifier::Shift]));
+        // shortcuts do not change the text
+        buffer.dispatch(Command::Keys(Key::Layout('a'), vec![Modifier::Control]));
+        keys(&mut buffer, SpecialKey::F1, vec![]);
+        assert_eq!(buffer.render(), "some \nA|");
+        keys(&mut buffer, SpecialKey::Numpad4, vec![]);
+        keys(&mut buffer, SpecialKey::NumpadDivide, vec![]);
+        keys(&mut buffer, SpecialKey::NumpadEnter, vec![]);
+        keys(&mut buffer, SpecialKey::VolumeUp, vec![]);
+        assert_eq!(buffer.render(), "some \nA4/\n|");
+    }
+
+    #[test]
+    fn test_compound_commands() {
+        let mut buffer = BufferController::with_text("ab\ncd");
+        buffer.dispatch(Command::MoveCursor(-1, -1));
+        buffer.dispatch(Command::Unicode(0xe9));
+        buffer.dispatch(Command::KeySequence(vec![
+            (Key::Special(SpecialKey::End), vec![]),
+            (Key::Layout('x'), vec![]),
+        ]));
+        assert_eq!(buffer.render(), "aébx|\ncd");
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut buffer = BufferController::with_text("one three");
+        assert_eq!(
+            buffer.apply(Command::Replace(5, "two".to_string())),
+            Some((5, "two".to_string()))
+        );
+        keys(&mut buffer, SpecialKey::LeftArrow, vec![Modifier::Control]);
+        // text that is added before the end rewrites everything after it
+        assert_eq!(
+            buffer.apply(Command::Replace(0, "and ".to_string())),
+            Some((3, "and two".to_string()))
+        );
+        assert_eq!(
+            buffer.apply(Command::Keys(Key::Special(SpecialKey::Return), vec![])),
+            Some((3, "\ntwo".to_string()))
+        );
+        assert_eq!(buffer.text(), "one and \ntwo");
+        assert_eq!(
+            buffer.apply(Command::Keys(Key::Special(SpecialKey::End), vec![])),
+            None
+        );
+        assert_eq!(buffer.apply(Command::Replace(0, String::new())), None);
+    }
+
+    #[test]
+    fn test_render_line() {
+        let mut buffer = BufferController::with_text("first\nsecond");
+        buffer.dispatch(Command::Replace(0, " word".to_string()));
+        buffer.dispatch(Command::Pending(5));
+        assert_eq!(buffer.render_line(), "second[ word]|");
+        keys(&mut buffer, SpecialKey::LeftArrow, vec![]);
+        assert_eq!(buffer.render_line(), "second wor|d");
+    }
+}
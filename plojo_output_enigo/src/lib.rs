@@ -5,17 +5,37 @@ use std::{process::Command as ProcessCommand, thread, time::Duration};
 
 pub struct EnigoController {
     enigo: Enigo,
+    // delays in milliseconds after each backspace and after each typed character
+    backspace_delay: u64,
+    key_delay: u64,
 }
 
 // NOTE: these are irrelevant because enigo imposes a delay of 20 milliseconds for every key press
-// Delay between pressing backspace (for corrections)
+// Default delay between pressing backspace (for corrections)
 const BACKSPACE_DELAY: u64 = 2;
-// Delay between pressing keys for typing normal text
+// Default delay between pressing keys for typing normal text
 const KEY_DELAY: u64 = 5;
 // Delay between starting to hold down keys for keyboard shortcuts
 const KEY_HOLD_DELAY: u64 = 2;
 
 impl EnigoController {
+    pub fn new(_disable_scan_keymap: bool) -> Self {
+        // enigo does not scan keymap, so ignore the option
+        Self {
+            enigo: Enigo::new(),
+            backspace_delay: BACKSPACE_DELAY,
+            key_delay: KEY_DELAY,
+        }
+    }
+
+    /// Set the delays in milliseconds after each backspace and after each typed character. They
+    /// can be 0 if the output is already delayed before it gets here
+    pub fn with_typing_delays(mut self, backspace_delay: u64, key_delay: u64) -> Self {
+        self.backspace_delay = backspace_delay;
+        self.key_delay = key_delay;
+        self
+    }
+
     fn type_with_delay(&mut self, text: &str, delay: u64) {
         for c in text.chars() {
             self.enigo.key_sequence(&c.to_string());
@@ -44,47 +64,53 @@ impl EnigoController {
 }
 
 impl Controller for EnigoController {
-    fn new(_disable_scan_keymap: bool) -> Self {
-        // enigo does not scan keymap, so ignore the option
-        Self {
-            enigo: Enigo::new(),
-        }
-    }
-
     fn dispatch(&mut self, command: Command) {
         match command {
             Command::Replace(backspace_num, add_text) => {
                 if backspace_num > 0 {
-                    self.backspace(backspace_num, BACKSPACE_DELAY);
+                    self.backspace(backspace_num, self.backspace_delay);
                 }
 
                 if !add_text.is_empty() {
-                    self.type_with_delay(&add_text, KEY_DELAY);
+                    self.type_with_delay(&add_text, self.key_delay);
                 }
             }
             Command::PrintHello => {
                 println!("Hello!");
             }
-            Command::NoOp => {}
+            Command::NoOp | Command::Pending(_) => {}
             Command::Keys(key, modifiers) => {
+                let key = match from_internal_key(&key) {
+                    Some(k) => k,
+                    None => {
+                        eprintln!("[WARN] Enigo cannot press {:?} on this platform", key);
+                        return;
+                    }
+                };
                 let mut keys = Vec::with_capacity(modifiers.len() + 1);
                 for m in modifiers {
                     keys.push(from_modifier(m));
                 }
-                keys.push(from_internal_key(key));
+                keys.push(key);
                 self.key_combo(keys, KEY_HOLD_DELAY);
             }
             Command::Raw(code) => {
                 self.enigo.key_click(Key::Raw(code));
             }
             Command::Shell(cmd, args) => dispatch_shell(cmd, args),
+            Command::Wait(millis) => thread::sleep(Duration::from_millis(millis)),
+            command @ (Command::KeySequence(_) | Command::Unicode(_) | Command::MoveCursor(..)) => {
+                for command in command.simplify() {
+                    self.dispatch(command);
+                }
+            }
             Command::TranslatorCommand(_) => panic!("cannot handle translator command"),
         }
     }
 }
 
-fn from_internal_key(key: InternalKey) -> Key {
-    match key {
+fn from_internal_key(key: &InternalKey) -> Option<Key> {
+    let key = match key {
         InternalKey::Special(special_key) => match special_key {
             SpecialKey::Backspace => Key::Backspace,
             SpecialKey::CapsLock => Key::CapsLock,
@@ -113,11 +139,98 @@ fn from_internal_key(key: InternalKey) -> Key {
             SpecialKey::Space => Key::Space,
             SpecialKey::Tab => Key::Tab,
             SpecialKey::UpArrow => Key::Raw(0x7e), // NOTE: fixes a bug in enigo
+            special_key => Key::Raw(raw_key_code(special_key)?),
         },
-        InternalKey::Layout(c) => Key::Layout(c),
+        InternalKey::Layout(c) => Key::Layout(*c),
+    };
+    Some(key)
+}
+
+/// The macOS key code of a special key that enigo has no name for
+#[cfg(target_os = "macos")]
+fn raw_key_code(key: &SpecialKey) -> Option<u16> {
+    match key {
+        SpecialKey::F13 => Some(0x69),
+        SpecialKey::F14 => Some(0x6b),
+        SpecialKey::F15 => Some(0x71),
+        SpecialKey::F16 => Some(0x6a),
+        SpecialKey::F17 => Some(0x40),
+        SpecialKey::F18 => Some(0x4f),
+        SpecialKey::F19 => Some(0x50),
+        SpecialKey::F20 => Some(0x5a),
+        // the help key is where insert is on a PC keyboard
+        SpecialKey::Insert => Some(0x72),
+        SpecialKey::Menu => Some(0x6e),
+        SpecialKey::Numpad0 => Some(0x52),
+        SpecialKey::Numpad1 => Some(0x53),
+        SpecialKey::Numpad2 => Some(0x54),
+        SpecialKey::Numpad3 => Some(0x55),
+        SpecialKey::Numpad4 => Some(0x56),
+        SpecialKey::Numpad5 => Some(0x57),
+        SpecialKey::Numpad6 => Some(0x58),
+        SpecialKey::Numpad7 => Some(0x59),
+        SpecialKey::Numpad8 => Some(0x5b),
+        SpecialKey::Numpad9 => Some(0x5c),
+        SpecialKey::NumpadAdd => Some(0x45),
+        SpecialKey::NumpadDecimal => Some(0x41),
+        SpecialKey::NumpadDivide => Some(0x4b),
+        SpecialKey::NumpadEnter => Some(0x4c),
+        SpecialKey::NumpadMultiply => Some(0x43),
+        SpecialKey::NumpadSubtract => Some(0x4e),
+        SpecialKey::VolumeDown => Some(0x49),
+        SpecialKey::VolumeMute => Some(0x4a),
+        SpecialKey::VolumeUp => Some(0x48),
+        // there are no key codes for the other keys on a Mac
+        _ => None,
     }
 }
 
+/// The Windows virtual key code of a special key that enigo has no name for
+#[cfg(windows)]
+fn raw_key_code(key: &SpecialKey) -> Option<u16> {
+    match key {
+        SpecialKey::F13 => Some(0x7c),
+        SpecialKey::F14 => Some(0x7d),
+        SpecialKey::F15 => Some(0x7e),
+        SpecialKey::F16 => Some(0x7f),
+        SpecialKey::F17 => Some(0x80),
+        SpecialKey::F18 => Some(0x81),
+        SpecialKey::F19 => Some(0x82),
+        SpecialKey::F20 => Some(0x83),
+        SpecialKey::F21 => Some(0x84),
+        SpecialKey::F22 => Some(0x85),
+        SpecialKey::F23 => Some(0x86),
+        SpecialKey::F24 => Some(0x87),
+        SpecialKey::Numpad0 => Some(0x60),
+        SpecialKey::Numpad1 => Some(0x61),
+        SpecialKey::Numpad2 => Some(0x62),
+        SpecialKey::Numpad3 => Some(0x63),
+        SpecialKey::Numpad4 => Some(0x64),
+        SpecialKey::Numpad5 => Some(0x65),
+        SpecialKey::Numpad6 => Some(0x66),
+        SpecialKey::Numpad7 => Some(0x67),
+        SpecialKey::Numpad8 => Some(0x68),
+        SpecialKey::Numpad9 => Some(0x69),
+        SpecialKey::NumpadAdd => Some(0x6b),
+        SpecialKey::NumpadDecimal => Some(0x6e),
+        // the keypad enter key has the same virtual key code as return
+        SpecialKey::NumpadEnter => Some(0x0d),
+        SpecialKey::NumpadMultiply => Some(0x6a),
+        SpecialKey::NumpadSubtract => Some(0x6d),
+        SpecialKey::Pause => Some(0x13),
+        SpecialKey::ScrollLock => Some(0x91),
+        // enigo presses raw keys without the extended key flag, which turns keys like insert, num
+        // lock, the media keys and keypad divide into other keys
+        _ => None,
+    }
+}
+
+/// Enigo cannot press raw key codes on Linux
+#[cfg(not(any(target_os = "macos", windows)))]
+fn raw_key_code(_key: &SpecialKey) -> Option<u16> {
+    None
+}
+
 fn from_modifier(modifier: Modifier) -> Key {
     match modifier {
         Modifier::Alt => Key::Alt,
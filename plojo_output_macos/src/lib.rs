@@ -21,8 +21,8 @@ pub struct MacController {
     char_to_keycode_map: Option<HashMap<char, CGKeyCode>>,
 }
 
-impl Controller for MacController {
-    fn new(disable_scan_keymap: bool) -> Self {
+impl MacController {
+    pub fn new(disable_scan_keymap: bool) -> Self {
         Self {
             char_to_keycode_map: if disable_scan_keymap {
                 // to disable keymap scanning, scan it only once at the beginning
@@ -32,7 +32,9 @@ impl Controller for MacController {
             },
         }
     }
+}
 
+impl Controller for MacController {
     fn dispatch(&mut self, command: Command) {
         match command {
             Command::Replace(backspace_num, add_text) => {
@@ -57,7 +59,7 @@ impl Controller for MacController {
             Command::PrintHello => {
                 println!("Hello!");
             }
-            Command::NoOp => {}
+            Command::NoOp | Command::Pending(_) => {}
             Command::Keys(key, modifiers) => {
                 let keycode = match key {
                     Key::Layout(c) => {
@@ -82,7 +84,13 @@ impl Controller for MacController {
                             panic!("could not convert {} to a physical key", c);
                         }
                     }
-                    Key::Special(special_key) => key_to_keycode(special_key),
+                    Key::Special(special_key) => match key_to_keycode(&special_key) {
+                        Some(code) => code,
+                        None => {
+                            eprintln!("[WARN] There is no {:?} key on macOS", special_key);
+                            return;
+                        }
+                    },
                 };
                 toggle_key(keycode, true, &modifiers, MODIFIER_DELAY);
                 thread::sleep(Duration::from_millis(KEY_HOLD_DELAY));
@@ -94,6 +102,12 @@ impl Controller for MacController {
                 toggle_key(key, false, &[], MODIFIER_DELAY);
             }
             Command::Shell(cmd, args) => dispatch_shell(cmd, args),
+            Command::Wait(millis) => thread::sleep(Duration::from_millis(millis)),
+            command @ (Command::KeySequence(_) | Command::Unicode(_) | Command::MoveCursor(..)) => {
+                for command in command.simplify() {
+                    self.dispatch(command);
+                }
+            }
             Command::TranslatorCommand(_) => panic!("cannot handle translator command"),
         }
     }
@@ -167,35 +181,81 @@ fn modifier_to_key(modifier: Modifier) -> CGKeyCode {
     }
 }
 
-fn key_to_keycode(key: SpecialKey) -> CGKeyCode {
+/// The key code of a special key, if a Mac keyboard has it
+fn key_to_keycode(key: &SpecialKey) -> Option<CGKeyCode> {
     match key {
-        SpecialKey::Backspace => KeyCode::DELETE,
-        SpecialKey::CapsLock => KeyCode::CAPS_LOCK,
-        SpecialKey::Delete => KeyCode::FORWARD_DELETE,
-        SpecialKey::DownArrow => KeyCode::DOWN_ARROW,
-        SpecialKey::End => KeyCode::END,
-        SpecialKey::Escape => KeyCode::ESCAPE,
-        SpecialKey::F1 => KeyCode::F1,
-        SpecialKey::F10 => KeyCode::F10,
-        SpecialKey::F11 => KeyCode::F11,
-        SpecialKey::F12 => KeyCode::F12,
-        SpecialKey::F2 => KeyCode::F2,
-        SpecialKey::F3 => KeyCode::F3,
-        SpecialKey::F4 => KeyCode::F4,
-        SpecialKey::F5 => KeyCode::F5,
-        SpecialKey::F6 => KeyCode::F6,
-        SpecialKey::F7 => KeyCode::F7,
-        SpecialKey::F8 => KeyCode::F8,
-        SpecialKey::F9 => KeyCode::F9,
-        SpecialKey::Home => KeyCode::HOME,
-        SpecialKey::LeftArrow => KeyCode::LEFT_ARROW,
-        SpecialKey::PageDown => KeyCode::PAGE_DOWN,
-        SpecialKey::PageUp => KeyCode::PAGE_UP,
-        SpecialKey::Return => KeyCode::RETURN,
-        SpecialKey::RightArrow => KeyCode::RIGHT_ARROW,
-        SpecialKey::Space => KeyCode::SPACE,
-        SpecialKey::Tab => KeyCode::TAB,
-        SpecialKey::UpArrow => KeyCode::UP_ARROW,
+        SpecialKey::Backspace => Some(KeyCode::DELETE),
+        SpecialKey::CapsLock => Some(KeyCode::CAPS_LOCK),
+        SpecialKey::Delete => Some(KeyCode::FORWARD_DELETE),
+        SpecialKey::DownArrow => Some(KeyCode::DOWN_ARROW),
+        SpecialKey::End => Some(KeyCode::END),
+        SpecialKey::Escape => Some(KeyCode::ESCAPE),
+        SpecialKey::F1 => Some(KeyCode::F1),
+        SpecialKey::F10 => Some(KeyCode::F10),
+        SpecialKey::F11 => Some(KeyCode::F11),
+        SpecialKey::F12 => Some(KeyCode::F12),
+        SpecialKey::F13 => Some(KeyCode::F13),
+        SpecialKey::F14 => Some(KeyCode::F14),
+        SpecialKey::F15 => Some(KeyCode::F15),
+        SpecialKey::F16 => Some(KeyCode::F16),
+        SpecialKey::F17 => Some(KeyCode::F17),
+        SpecialKey::F18 => Some(KeyCode::F18),
+        SpecialKey::F19 => Some(KeyCode::F19),
+        SpecialKey::F2 => Some(KeyCode::F2),
+        SpecialKey::F20 => Some(KeyCode::F20),
+        SpecialKey::F21 => None,
+        SpecialKey::F22 => None,
+        SpecialKey::F23 => None,
+        SpecialKey::F24 => None,
+        SpecialKey::F3 => Some(KeyCode::F3),
+        SpecialKey::F4 => Some(KeyCode::F4),
+        SpecialKey::F5 => Some(KeyCode::F5),
+        SpecialKey::F6 => Some(KeyCode::F6),
+        SpecialKey::F7 => Some(KeyCode::F7),
+        SpecialKey::F8 => Some(KeyCode::F8),
+        SpecialKey::F9 => Some(KeyCode::F9),
+        SpecialKey::Home => Some(KeyCode::HOME),
+        // the help key is where insert is on a PC keyboard
+        SpecialKey::Insert => Some(KeyCode::HELP),
+        SpecialKey::LeftArrow => Some(KeyCode::LEFT_ARROW),
+        // media keys are system events instead of key presses
+        SpecialKey::MediaNextTrack => None,
+        SpecialKey::MediaPlayPause => None,
+        SpecialKey::MediaPreviousTrack => None,
+        SpecialKey::MediaStop => None,
+        // the contextual menu key on some keyboards
+        SpecialKey::Menu => Some(0x6E),
+        SpecialKey::NumLock => None,
+        // key codes from Events.h that core-graphics has no names for
+        SpecialKey::Numpad0 => Some(0x52),
+        SpecialKey::Numpad1 => Some(0x53),
+        SpecialKey::Numpad2 => Some(0x54),
+        SpecialKey::Numpad3 => Some(0x55),
+        SpecialKey::Numpad4 => Some(0x56),
+        SpecialKey::Numpad5 => Some(0x57),
+        SpecialKey::Numpad6 => Some(0x58),
+        SpecialKey::Numpad7 => Some(0x59),
+        SpecialKey::Numpad8 => Some(0x5B),
+        SpecialKey::Numpad9 => Some(0x5C),
+        SpecialKey::NumpadAdd => Some(0x45),
+        SpecialKey::NumpadDecimal => Some(0x41),
+        SpecialKey::NumpadDivide => Some(0x4B),
+        SpecialKey::NumpadEnter => Some(0x4C),
+        SpecialKey::NumpadMultiply => Some(0x43),
+        SpecialKey::NumpadSubtract => Some(0x4E),
+        SpecialKey::PageDown => Some(KeyCode::PAGE_DOWN),
+        SpecialKey::PageUp => Some(KeyCode::PAGE_UP),
+        SpecialKey::Pause => None,
+        SpecialKey::PrintScreen => None,
+        SpecialKey::Return => Some(KeyCode::RETURN),
+        SpecialKey::RightArrow => Some(KeyCode::RIGHT_ARROW),
+        SpecialKey::ScrollLock => None,
+        SpecialKey::Space => Some(KeyCode::SPACE),
+        SpecialKey::Tab => Some(KeyCode::TAB),
+        SpecialKey::UpArrow => Some(KeyCode::UP_ARROW),
+        SpecialKey::VolumeDown => Some(KeyCode::VOLUME_DOWN),
+        SpecialKey::VolumeMute => Some(KeyCode::MUTE),
+        SpecialKey::VolumeUp => Some(KeyCode::VOLUME_UP),
     }
 }
 
@@ -0,0 +1,369 @@
+//! Dispatch commands to Neovim through its msgpack-RPC API.
+//!
+//! Text is edited directly in the buffer instead of being typed, so it is not affected by the
+//! current mode or by mappings.
+
+use plojo_core::{Command, Controller, Key, Modifier, SpecialKey};
+use rmpv::Value;
+use rpc::Session;
+use std::{
+    env,
+    error::Error,
+    net::TcpStream,
+    os::unix::net::UnixStream,
+    process::{Child, Command as ProcessCommand, Stdio},
+    thread,
+    time::Duration,
+};
+
+mod rpc;
+
+// Deletes characters before the cursor (a line break counts as one character), inserts the text
+// at the cursor and moves the cursor to the end of the inserted text. Outside of insert mode, the
+// cursor is on a character instead of between two, so text is added after it like with `a`
+const REPLACE_LUA: &str = r#"
+local backspaces, text = ...
+local function line_at(row)
+  return vim.api.nvim_buf_get_lines(0, row, row + 1, true)[1]
+end
+local function is_continuation(line, col)
+  local byte = line:byte(col + 1)
+  return byte ~= nil and byte >= 0x80 and byte < 0xC0
+end
+-- the byte index of the character before the byte index in the line
+local function prev_char(line, col)
+  col = col - 1
+  while col > 0 and is_continuation(line, col) do col = col - 1 end
+  return col
+end
+
+local insert_mode = vim.api.nvim_get_mode().mode:sub(1, 1) == "i"
+local row, col = unpack(vim.api.nvim_win_get_cursor(0))
+row = row - 1
+if not insert_mode and col < #line_at(row) then
+  col = col + 1
+  while is_continuation(line_at(row), col) do col = col + 1 end
+end
+
+local start_row, start_col = row, col
+while backspaces > 0 do
+  if start_col > 0 then
+    start_col = prev_char(line_at(start_row), start_col)
+  elseif start_row > 0 then
+    start_row = start_row - 1
+    start_col = #line_at(start_row)
+  else
+    break
+  end
+  backspaces = backspaces - 1
+end
+
+local lines = vim.split(text, "\n", { plain = true })
+vim.api.nvim_buf_set_text(0, start_row, start_col, row, col, lines)
+local end_row = start_row + #lines - 1
+local end_col = #lines[#lines]
+if #lines == 1 then end_col = end_col + start_col end
+if not insert_mode and end_col > 0 then end_col = prev_char(line_at(end_row), end_col) end
+vim.api.nvim_win_set_cursor(0, { end_row + 1, end_col })
+"#;
+
+pub struct NvimController {
+    session: Session,
+    // the embedded Neovim process, which is kept so it is not left running
+    child: Option<Child>,
+}
+
+impl NvimController {
+    /// Connect to the Neovim server at the address, which is either the path to a socket or a
+    /// TCP address like "127.0.0.1:6666"
+    pub fn connect(address: &str) -> Result<Self, Box<dyn Error>> {
+        let session = if address.contains('/') || !address.contains(':') {
+            let stream = UnixStream::connect(address)?;
+            Session::new(Box::new(stream.try_clone()?), Box::new(stream))
+        } else {
+            let stream = TcpStream::connect(address)?;
+            Session::new(Box::new(stream.try_clone()?), Box::new(stream))
+        };
+        Ok(Self {
+            session,
+            child: None,
+        })
+    }
+
+    /// Connect to the Neovim instance that plojo was started from (with `$NVIM`) or the one that
+    /// is listening on `$NVIM_LISTEN_ADDRESS`
+    pub fn connect_from_env() -> Result<Self, Box<dyn Error>> {
+        let address = env::var("NVIM")
+            .or_else(|_| env::var("NVIM_LISTEN_ADDRESS"))
+            .map_err(|_| "neither $NVIM nor $NVIM_LISTEN_ADDRESS is set")?;
+        Self::connect(&address)
+    }
+
+    /// Start a headless Neovim that is controlled through its standard input and output
+    pub fn embed() -> Result<Self, Box<dyn Error>> {
+        let mut child = ProcessCommand::new("nvim")
+            .args(["--embed", "--headless", "--clean"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().ok_or("unable to open nvim stdin")?;
+        let stdout = child.stdout.take().ok_or("unable to open nvim stdout")?;
+        Ok(Self {
+            session: Session::new(Box::new(stdout), Box::new(stdin)),
+            child: Some(child),
+        })
+    }
+
+    fn try_dispatch(&mut self, command: Command) -> Result<(), Box<dyn Error>> {
+        match command {
+            Command::Replace(backspace_num, add_text) => {
+                self.session.call(
+                    "nvim_exec_lua",
+                    vec![
+                        REPLACE_LUA.into(),
+                        Value::Array(vec![backspace_num.into(), add_text.into()]),
+                    ],
+                )?;
+            }
+            Command::PrintHello => {
+                println!("Hello!");
+            }
+            Command::NoOp | Command::Pending(_) => {}
+            Command::Keys(key, modifiers) => match key_notation(&key, &modifiers) {
+                Some(keys) => {
+                    self.session.call("nvim_input", vec![keys.into()])?;
+                }
+                None => eprintln!("[WARN] Cannot send {:?} to Neovim", key),
+            },
+            Command::Raw(code) => eprintln!("[WARN] Cannot send raw key code {} to Neovim", code),
+            Command::Shell(cmd, args) => dispatch_shell(cmd, args),
+            Command::Wait(millis) => thread::sleep(Duration::from_millis(millis)),
+            command @ (Command::KeySequence(_) | Command::Unicode(_) | Command::MoveCursor(..)) => {
+                for command in command.simplify() {
+                    self.try_dispatch(command)?;
+                }
+            }
+            Command::TranslatorCommand(_) => panic!("cannot handle translator command"),
+        }
+        Ok(())
+    }
+}
+
+impl Controller for NvimController {
+    fn dispatch(&mut self, command: Command) {
+        if let Err(e) = self.try_dispatch(command) {
+            eprintln!("[WARN] Could not dispatch command to Neovim: {}", e);
+        }
+    }
+}
+
+impl Drop for NvimController {
+    fn drop(&mut self) {
+        if let Some(ref mut child) = self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Write the key with modifiers in Neovim's key notation (ex: `<C-S-Left>`)
+fn key_notation(key: &Key, modifiers: &[Modifier]) -> Option<String> {
+    let name = match key {
+        Key::Special(special_key) => special_key_name(special_key)?.to_string(),
+        Key::Layout('<') => "lt".to_string(),
+        Key::Layout('\\') => "Bslash".to_string(),
+        Key::Layout('|') => "Bar".to_string(),
+        Key::Layout(' ') => "Space".to_string(),
+        Key::Layout(c) => c.to_string(),
+    };
+
+    let mut prefix = String::new();
+    for m in modifiers {
+        match m {
+            Modifier::Alt | Modifier::Option => prefix.push_str("A-"),
+            Modifier::Control => prefix.push_str("C-"),
+            Modifier::Meta => prefix.push_str("D-"),
+            Modifier::Shift => prefix.push_str("S-"),
+            // the function key is never seen by Neovim
+            Modifier::Fn => {}
+        }
+    }
+
+    // single characters without modifiers are sent as they are
+    if prefix.is_empty() && name.chars().count() == 1 {
+        Some(name)
+    } else {
+        Some(format!("<{}{}>", prefix, name))
+    }
+}
+
+/// The name of a special key in Neovim's key notation
+fn special_key_name(key: &SpecialKey) -> Option<&'static str> {
+    let name = match key {
+        SpecialKey::Backspace => "BS",
+        // Neovim only sees the letters that caps lock changes
+        SpecialKey::CapsLock => return None,
+        SpecialKey::Delete => "Del",
+        SpecialKey::DownArrow => "Down",
+        SpecialKey::End => "End",
+        SpecialKey::Escape => "Esc",
+        SpecialKey::F1 => "F1",
+        SpecialKey::F2 => "F2",
+        SpecialKey::F3 => "F3",
+        SpecialKey::F4 => "F4",
+        SpecialKey::F5 => "F5",
+        SpecialKey::F6 => "F6",
+        SpecialKey::F7 => "F7",
+        SpecialKey::F8 => "F8",
+        SpecialKey::F9 => "F9",
+        SpecialKey::F10 => "F10",
+        SpecialKey::F11 => "F11",
+        SpecialKey::F12 => "F12",
+        SpecialKey::F13 => "F13",
+        SpecialKey::F14 => "F14",
+        SpecialKey::F15 => "F15",
+        SpecialKey::F16 => "F16",
+        SpecialKey::F17 => "F17",
+        SpecialKey::F18 => "F18",
+        SpecialKey::F19 => "F19",
+        SpecialKey::F20 => "F20",
+        SpecialKey::F21 => "F21",
+        SpecialKey::F22 => "F22",
+        SpecialKey::F23 => "F23",
+        SpecialKey::F24 => "F24",
+        SpecialKey::Home => "Home",
+        SpecialKey::Insert => "Insert",
+        SpecialKey::LeftArrow => "Left",
+        SpecialKey::Numpad0 => "k0",
+        SpecialKey::Numpad1 => "k1",
+        SpecialKey::Numpad2 => "k2",
+        SpecialKey::Numpad3 => "k3",
+        SpecialKey::Numpad4 => "k4",
+        SpecialKey::Numpad5 => "k5",
+        SpecialKey::Numpad6 => "k6",
+        SpecialKey::Numpad7 => "k7",
+        SpecialKey::Numpad8 => "k8",
+        SpecialKey::Numpad9 => "k9",
+        SpecialKey::NumpadAdd => "kPlus",
+        SpecialKey::NumpadDecimal => "kPoint",
+        SpecialKey::NumpadDivide => "kDivide",
+        SpecialKey::NumpadEnter => "kEnter",
+        SpecialKey::NumpadMultiply => "kMultiply",
+        SpecialKey::NumpadSubtract => "kMinus",
+        SpecialKey::PageDown => "PageDown",
+        SpecialKey::PageUp => "PageUp",
+        SpecialKey::Return => "CR",
+        SpecialKey::RightArrow => "Right",
+        SpecialKey::Space => "Space",
+        SpecialKey::Tab => "Tab",
+        SpecialKey::UpArrow => "Up",
+        // system and media keys never reach Neovim
+        SpecialKey::Menu
+        | SpecialKey::NumLock
+        | SpecialKey::Pause
+        | SpecialKey::PrintScreen
+        | SpecialKey::ScrollLock
+        | SpecialKey::MediaNextTrack
+        | SpecialKey::MediaPlayPause
+        | SpecialKey::MediaPreviousTrack
+        | SpecialKey::MediaStop
+        | SpecialKey::VolumeDown
+        | SpecialKey::VolumeMute
+        | SpecialKey::VolumeUp => return None,
+    };
+    Some(name)
+}
+
+fn dispatch_shell(cmd: String, args: Vec<String>) {
+    let result = ProcessCommand::new(cmd).args(args).spawn();
+    match result {
+        Ok(_) => {}
+        Err(e) => eprintln!("[WARN] Could not execute shell command: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_notation() {
+        let key = |k, m: Vec<Modifier>| key_notation(&k, &m);
+        assert_eq!(key(Key::Layout('a'), vec![]), Some("a".to_string()));
+        assert_eq!(key(Key::Layout('<'), vec![]), Some("<lt>".to_string()));
+        assert_eq!(
+            key(Key::Layout('z'), vec![Modifier::Control]),
+            Some("<C-z>".to_string())
+        );
+        assert_eq!(
+            key(
+                Key::Special(SpecialKey::LeftArrow),
+                vec![Modifier::Control, Modifier::Shift]
+            ),
+            Some("<C-S-Left>".to_string())
+        );
+        assert_eq!(
+            key(Key::Special(SpecialKey::Return), vec![]),
+            Some("<CR>".to_string())
+        );
+        assert_eq!(
+            key(Key::Special(SpecialKey::NumpadAdd), vec![]),
+            Some("<kPlus>".to_string())
+        );
+        assert_eq!(key(Key::Special(SpecialKey::CapsLock), vec![]), None);
+        assert_eq!(key(Key::Special(SpecialKey::VolumeUp), vec![]), None);
+    }
+
+    fn buffer_lines(controller: &mut NvimController) -> Value {
+        controller
+            .session
+            .call(
+                "nvim_buf_get_lines",
+                vec![0.into(), 0.into(), (-1).into(), true.into()],
+            )
+            .unwrap()
+    }
+
+    #[test]
+    #[ignore = "needs nvim to be installed"]
+    fn test_embedded_replace() {
+        let mut controller = NvimController::embed().unwrap();
+        controller
+            .try_dispatch(Command::Replace(0, "Hello wörld".to_string()))
+            .unwrap();
+        controller
+            .try_dispatch(Command::Replace(4, "ow\nthere".to_string()))
+            .unwrap();
+        assert_eq!(
+            buffer_lines(&mut controller),
+            Value::Array(vec!["Hello wow".into(), "there".into()])
+        );
+        // deleting across the line break
+        controller
+            .try_dispatch(Command::Replace(6, "!".to_string()))
+            .unwrap();
+        assert_eq!(
+            buffer_lines(&mut controller),
+            Value::Array(vec!["Hello wow!".into()])
+        );
+    }
+
+    #[test]
+    #[ignore = "needs nvim to be installed"]
+    fn test_embedded_keys() {
+        let mut controller = NvimController::embed().unwrap();
+        controller
+            .try_dispatch(Command::Keys(Key::Layout('i'), vec![]))
+            .unwrap();
+        controller
+            .try_dispatch(Command::Keys(Key::Layout('<'), vec![]))
+            .unwrap();
+        controller
+            .try_dispatch(Command::Keys(Key::Special(SpecialKey::Escape), vec![]))
+            .unwrap();
+        assert_eq!(
+            buffer_lines(&mut controller),
+            Value::Array(vec!["<".into()])
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! A minimal synchronous msgpack-RPC client for the Neovim API
+use rmpv::{decode::read_value, encode::write_value, Value};
+use std::{
+    error::Error,
+    io::{BufReader, BufWriter, Read, Write},
+};
+
+// msgpack-RPC message types
+const REQUEST: u64 = 0;
+const RESPONSE: u64 = 1;
+const NOTIFICATION: u64 = 2;
+
+pub(crate) struct Session {
+    reader: BufReader<Box<dyn Read + Send>>,
+    writer: BufWriter<Box<dyn Write + Send>>,
+    next_id: u64,
+}
+
+impl Session {
+    pub(crate) fn new(reader: Box<dyn Read + Send>, writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            writer: BufWriter::new(writer),
+            next_id: 0,
+        }
+    }
+
+    /// Call an API function and wait for its result
+    pub(crate) fn call(&mut self, method: &str, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = Value::Array(vec![
+            REQUEST.into(),
+            id.into(),
+            method.into(),
+            Value::Array(args),
+        ]);
+        write_value(&mut self.writer, &request)?;
+        self.writer.flush()?;
+
+        loop {
+            let message = read_value(&mut self.reader)?;
+            let message = message.as_array().ok_or("RPC message is not an array")?;
+            match message.first().and_then(Value::as_u64) {
+                Some(RESPONSE) if message.get(1).and_then(Value::as_u64) == Some(id) => {
+                    return match (message.get(2), message.get(3)) {
+                        (Some(Value::Nil), Some(result)) => Ok(result.clone()),
+                        (Some(error), _) => Err(format!("{} failed: {}", method, error).into()),
+                        _ => Err("RPC response is missing its result".into()),
+                    };
+                }
+                // responses to other requests and notifications are not needed
+                Some(RESPONSE) | Some(NOTIFICATION) => {}
+                Some(REQUEST) => {
+                    // nothing is registered to handle requests from Neovim
+                    let response = Value::Array(vec![
+                        RESPONSE.into(),
+                        message.get(1).cloned().unwrap_or(Value::Nil),
+                        "plojo does not handle requests".into(),
+                        Value::Nil,
+                    ]);
+                    write_value(&mut self.writer, &response)?;
+                    self.writer.flush()?;
+                }
+                _ => return Err("unknown RPC message type".into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::Cursor,
+        sync::{Arc, Mutex},
+    };
+
+    /// Writer that can be read after it is moved into the session
+    #[derive(Clone, Default)]
+    struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn encode(values: &[Value]) -> Vec<u8> {
+        let mut bytes = vec![];
+        for v in values {
+            write_value(&mut bytes, v).unwrap();
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_call() {
+        let replies = encode(&[
+            // a notification and a response to some other request come first
+            Value::Array(vec![2.into(), "redraw".into(), Value::Array(vec![])]),
+            Value::Array(vec![1.into(), 5.into(), Value::Nil, Value::Nil]),
+            Value::Array(vec![1.into(), 0.into(), Value::Nil, 42.into()]),
+            Value::Array(vec![
+                1.into(),
+                1.into(),
+                Value::Array(vec![0.into(), "Invalid key".into()]),
+                Value::Nil,
+            ]),
+        ]);
+        let written = SharedWriter::default();
+        let mut session = Session::new(Box::new(Cursor::new(replies)), Box::new(written.clone()));
+
+        assert_eq!(
+            session.call("nvim_eval", vec!["40 + 2".into()]).unwrap(),
+            Value::from(42)
+        );
+        assert!(session.call("nvim_input", vec!["<Bad>".into()]).is_err());
+
+        let request = read_value(&mut &written.0.lock().unwrap()[..]).unwrap();
+        assert_eq!(
+            request,
+            Value::Array(vec![
+                0.into(),
+                0.into(),
+                "nvim_eval".into(),
+                Value::Array(vec!["40 + 2".into()]),
+            ])
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! Dispatch commands to a tmux pane with `tmux send-keys`.
+//!
+//! Keys go to the target pane no matter which window has focus, so this also works over ssh
+//! without a GUI.
+
+use plojo_core::{Command, Controller, Key, Modifier, SpecialKey};
+use std::{process::Command as ProcessCommand, thread, time::Duration};
+
+/// Sends keys to the current pane of the default tmux server unless a target is given
+#[derive(Default)]
+pub struct TmuxController {
+    // the pane to send keys to, in any format that `tmux send-keys -t` accepts
+    target: Option<String>,
+    // the tmux server socket name (`tmux -L`), if it is not the default server
+    socket_name: Option<String>,
+}
+
+impl TmuxController {
+    /// Send keys to the target pane (ex: "work:1.0" or "%3")
+    pub fn with_target(target: &str) -> Self {
+        Self {
+            target: Some(target.to_string()),
+            socket_name: None,
+        }
+    }
+
+    /// Use the tmux server with this socket name instead of the default one
+    pub fn with_socket_name(mut self, socket_name: &str) -> Self {
+        self.socket_name = Some(socket_name.to_string());
+        self
+    }
+
+    /// Run `tmux send-keys` with the arguments for the target pane
+    fn send_keys(&self, args: Vec<String>) {
+        let mut command = ProcessCommand::new("tmux");
+        if let Some(ref socket_name) = self.socket_name {
+            command.args(["-L", socket_name]);
+        }
+        command.arg("send-keys");
+        if let Some(ref target) = self.target {
+            command.args(["-t", target]);
+        }
+        match command.args(args).output() {
+            Ok(output) if !output.status.success() => eprintln!(
+                "[WARN] tmux send-keys failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Ok(_) => {}
+            Err(e) => eprintln!("[WARN] Could not run tmux: {}", e),
+        }
+    }
+}
+
+impl Controller for TmuxController {
+    fn dispatch(&mut self, command: Command) {
+        match command {
+            Command::Replace(backspace_num, add_text) => {
+                for args in replace_args(backspace_num, &add_text) {
+                    self.send_keys(args);
+                }
+            }
+            Command::PrintHello => {
+                println!("Hello!");
+            }
+            Command::NoOp | Command::Pending(_) => {}
+            Command::Keys(key, modifiers) => match key_name(&key, &modifiers) {
+                Some(name) => self.send_keys(vec![name]),
+                None => eprintln!("[WARN] Cannot send {:?} with {:?} to tmux", key, modifiers),
+            },
+            Command::Raw(code) => eprintln!("[WARN] Cannot send raw key code {} to tmux", code),
+            Command::Shell(cmd, args) => dispatch_shell(cmd, args),
+            Command::Wait(millis) => thread::sleep(Duration::from_millis(millis)),
+            command @ (Command::KeySequence(_) | Command::Unicode(_) | Command::MoveCursor(..)) => {
+                for command in command.simplify() {
+                    self.dispatch(command);
+                }
+            }
+            Command::TranslatorCommand(_) => panic!("cannot handle translator command"),
+        }
+    }
+}
+
+/// The `send-keys` arguments for each tmux call to delete characters and type text. Text is
+/// sent literally (`-l`), except for line breaks, which are sent as the enter key
+fn replace_args(backspace_num: usize, add_text: &str) -> Vec<Vec<String>> {
+    let mut calls = vec![];
+    if backspace_num > 0 {
+        calls.push(vec!["BSpace".to_string(); backspace_num]);
+    }
+    for (i, line) in add_text.split('\n').enumerate() {
+        if i > 0 {
+            calls.push(vec!["Enter".to_string()]);
+        }
+        if !line.is_empty() {
+            // `--` so that text starting with a dash is not read as a flag
+            calls.push(vec![
+                "-l".to_string(),
+                "--".to_string(),
+                escape_separator(line),
+            ]);
+        }
+    }
+    calls
+}
+
+/// tmux reads an argument that ends with `;` as the end of the command, unless it is escaped as
+/// `\;`. The backslash is removed again, even if the text already ends with `\;`
+fn escape_separator(arg: &str) -> String {
+    match arg.strip_suffix(';') {
+        Some(before) => format!("{}\\;", before),
+        None => arg.to_string(),
+    }
+}
+
+/// The tmux name of the key with modifiers (ex: "C-M-Left")
+fn key_name(key: &Key, modifiers: &[Modifier]) -> Option<String> {
+    let mut name = String::new();
+    for m in modifiers {
+        match m {
+            Modifier::Alt | Modifier::Option => name.push_str("M-"),
+            Modifier::Control => name.push_str("C-"),
+            Modifier::Shift => name.push_str("S-"),
+            // terminals do not receive the super or function keys
+            Modifier::Meta | Modifier::Fn => return None,
+        }
+    }
+    match key {
+        Key::Special(special_key) => name.push_str(special_key_name(special_key)?),
+        Key::Layout(' ') => name.push_str("Space"),
+        Key::Layout(c) => name.push(*c),
+    }
+    Some(escape_separator(&name))
+}
+
+fn special_key_name(key: &SpecialKey) -> Option<&'static str> {
+    let name = match key {
+        SpecialKey::Backspace => "BSpace",
+        SpecialKey::CapsLock => return None,
+        SpecialKey::Delete => "DC",
+        SpecialKey::DownArrow => "Down",
+        SpecialKey::End => "End",
+        SpecialKey::Escape => "Escape",
+        SpecialKey::F1 => "F1",
+        SpecialKey::F2 => "F2",
+        SpecialKey::F3 => "F3",
+        SpecialKey::F4 => "F4",
+        SpecialKey::F5 => "F5",
+        SpecialKey::F6 => "F6",
+        SpecialKey::F7 => "F7",
+        SpecialKey::F8 => "F8",
+        SpecialKey::F9 => "F9",
+        SpecialKey::F10 => "F10",
+        SpecialKey::F11 => "F11",
+        SpecialKey::F12 => "F12",
+        SpecialKey::Home => "Home",
+        SpecialKey::Insert => "IC",
+        SpecialKey::LeftArrow => "Left",
+        SpecialKey::Numpad0 => "KP0",
+        SpecialKey::Numpad1 => "KP1",
+        SpecialKey::Numpad2 => "KP2",
+        SpecialKey::Numpad3 => "KP3",
+        SpecialKey::Numpad4 => "KP4",
+        SpecialKey::Numpad5 => "KP5",
+        SpecialKey::Numpad6 => "KP6",
+        SpecialKey::Numpad7 => "KP7",
+        SpecialKey::Numpad8 => "KP8",
+        SpecialKey::Numpad9 => "KP9",
+        SpecialKey::NumpadAdd => "KP+",
+        SpecialKey::NumpadDecimal => "KP.",
+        SpecialKey::NumpadDivide => "KP/",
+        SpecialKey::NumpadEnter => "KPEnter",
+        SpecialKey::NumpadMultiply => "KP*",
+        SpecialKey::NumpadSubtract => "KP-",
+        SpecialKey::PageDown => "NPage",
+        SpecialKey::PageUp => "PPage",
+        SpecialKey::Return => "Enter",
+        SpecialKey::RightArrow => "Right",
+        SpecialKey::Space => "Space",
+        SpecialKey::Tab => "Tab",
+        SpecialKey::UpArrow => "Up",
+        // tmux only has names for the first 12 function keys, and no system or media keys
+        SpecialKey::F13
+        | SpecialKey::F14
+        | SpecialKey::F15
+        | SpecialKey::F16
+        | SpecialKey::F17
+        | SpecialKey::F18
+        | SpecialKey::F19
+        | SpecialKey::F20
+        | SpecialKey::F21
+        | SpecialKey::F22
+        | SpecialKey::F23
+        | SpecialKey::F24
+        | SpecialKey::Menu
+        | SpecialKey::NumLock
+        | SpecialKey::Pause
+        | SpecialKey::PrintScreen
+        | SpecialKey::ScrollLock
+        | SpecialKey::MediaNextTrack
+        | SpecialKey::MediaPlayPause
+        | SpecialKey::MediaPreviousTrack
+        | SpecialKey::MediaStop
+        | SpecialKey::VolumeDown
+        | SpecialKey::VolumeMute
+        | SpecialKey::VolumeUp => return None,
+    };
+    Some(name)
+}
+
+fn dispatch_shell(cmd: String, args: Vec<String>) {
+    let result = ProcessCommand::new(cmd).args(args).spawn();
+    match result {
+        Ok(_) => {}
+        Err(e) => eprintln!("[WARN] Could not execute shell command: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(s: &[&str]) -> Vec<String> {
+        s.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_replace_args() {
+        assert_eq!(replace_args(0, ""), Vec::<Vec<String>>::new());
+        assert_eq!(
+            replace_args(2, " -v"),
+            vec![
+                strings(&["BSpace", "BSpace"]),
+                strings(&["-l", "--", " -v"])
+            ]
+        );
+        assert_eq!(
+            replace_args(0, "ls\n"),
+            vec![strings(&["-l", "--", "ls"]), strings(&["Enter"])]
+        );
+        assert_eq!(
+            replace_args(0, "a; b;\n;"),
+            vec![
+                strings(&["-l", "--", "a; b\\;"]),
+                strings(&["Enter"]),
+                strings(&["-l", "--", "\\;"])
+            ]
+        );
+        assert_eq!(
+            replace_args(0, "\\;"),
+            vec![strings(&["-l", "--", "\\\\;"])]
+        );
+    }
+
+    #[test]
+    fn test_key_name() {
+        assert_eq!(
+            key_name(&Key::Layout('c'), &[Modifier::Control]),
+            Some("C-c".to_string())
+        );
+        assert_eq!(
+            key_name(&Key::Special(SpecialKey::LeftArrow), &[Modifier::Alt]),
+            Some("M-Left".to_string())
+        );
+        assert_eq!(
+            key_name(&Key::Special(SpecialKey::Numpad7), &[]),
+            Some("KP7".to_string())
+        );
+        assert_eq!(key_name(&Key::Layout(';'), &[]), Some("\\;".to_string()));
+        assert_eq!(
+            key_name(&Key::Layout(';'), &[Modifier::Control]),
+            Some("C-\\;".to_string())
+        );
+        assert_eq!(key_name(&Key::Layout('c'), &[Modifier::Meta]), None);
+        assert_eq!(key_name(&Key::Special(SpecialKey::F13), &[]), None);
+    }
+
+    #[test]
+    #[ignore = "needs tmux to be installed"]
+    fn test_send_to_pane() {
+        let tmux = |args: &[&str]| {
+            ProcessCommand::new("tmux")
+                .args(["-L", "plojo-test"])
+                .args(args)
+                .output()
+                .unwrap()
+        };
+        tmux(&[
+            "new-session",
+            "-d",
+            "-s",
+            "plojo",
+            "-x",
+            "80",
+            "-y",
+            "5",
+            "cat",
+        ]);
+
+        let mut controller = TmuxController::with_target("plojo").with_socket_name("plojo-test");
+        controller.dispatch(Command::Replace(0, "helo".to_string()));
+        controller.dispatch(Command::Replace(1, "lo -world\nnext".to_string()));
+        controller.dispatch(Command::Replace(0, " a; \\;".to_string()));
+        thread::sleep(Duration::from_millis(200));
+        let output = tmux(&["capture-pane", "-p", "-t", "plojo"]);
+        tmux(&["kill-server"]);
+
+        let text = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(
+            text.lines().take(3).collect::<Vec<_>>(),
+            vec!["hello -world", "hello -world", "next a; \\;"]
+        );
+    }
+}
@@ -0,0 +1,186 @@
+//! Splits the transcript into timed caption cues and writes them as SRT or WebVTT
+use std::{fmt::Write, time::Duration};
+
+// cues are split at a word boundary once they are this long
+const MAX_CUE_CHARS: usize = 80;
+// how long the last cue stays on screen after its last character is written
+const LAST_CUE_DURATION: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaptionFormat {
+    Srt,
+    WebVtt,
+}
+
+impl CaptionFormat {
+    /// Find the format from the extension of a captions file (`.srt` or `.vtt`)
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "srt" => Some(Self::Srt),
+            "vtt" => Some(Self::WebVtt),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Cue {
+    start: Duration,
+    end: Duration,
+    text: String,
+}
+
+/// Split the text into cues at line breaks, sentence ends and long lines. The time of each cue is
+/// from the time its first character was written until the next cue starts
+fn cues(text: &[char], times: &[Duration]) -> Vec<Cue> {
+    // (start, end) character ranges of each cue
+    let mut ranges = vec![];
+    let mut start = 0;
+    for (i, &c) in text.iter().enumerate() {
+        let next = text.get(i + 1).copied();
+        let sentence_end = matches!(c, '.' | '!' | '?') && next.is_some_and(char::is_whitespace);
+        // split before the next word if it would make the cue too long
+        let too_long = c.is_whitespace() && {
+            let word_end = text[i + 1..]
+                .iter()
+                .position(|c| c.is_whitespace())
+                .map_or(text.len(), |j| i + 1 + j);
+            word_end - start > MAX_CUE_CHARS
+        };
+        if c == '\n' || sentence_end || too_long {
+            ranges.push((start, i + 1));
+            start = i + 1;
+        }
+    }
+    ranges.push((start, text.len()));
+
+    let mut cues: Vec<Cue> = vec![];
+    for (start, end) in ranges {
+        let cue_text: String = text[start..end].iter().collect();
+        let cue_text = cue_text.trim();
+        if cue_text.is_empty() {
+            continue;
+        }
+        // the cue starts when its first visible character was written
+        let first = (start..end)
+            .find(|&i| !text[i].is_whitespace())
+            .unwrap_or(start);
+        let cue_start = times[first];
+        if let Some(prev) = cues.last_mut() {
+            prev.end = cue_start.max(prev.start);
+        }
+        cues.push(Cue {
+            start: cue_start,
+            end: times[end - 1] + LAST_CUE_DURATION,
+            text: cue_text.to_string(),
+        });
+    }
+    cues
+}
+
+/// Write the captions for the text, where each character was written at the time with the same
+/// index
+pub(crate) fn write_captions(format: CaptionFormat, text: &[char], times: &[Duration]) -> String {
+    let mut captions = String::new();
+    if format == CaptionFormat::WebVtt {
+        captions.push_str("WEBVTT\n\n");
+    }
+    for (i, cue) in cues(text, times).iter().enumerate() {
+        if format == CaptionFormat::Srt {
+            writeln!(captions, "{}", i + 1).unwrap();
+        }
+        writeln!(
+            captions,
+            "{} --> {}\n{}\n",
+            timestamp(format, cue.start),
+            timestamp(format, cue.end),
+            cue.text
+        )
+        .unwrap();
+    }
+    captions
+}
+
+/// Format the time like `01:02:03,456` for SRT or `01:02:03.456` for WebVTT
+fn timestamp(format: CaptionFormat, time: Duration) -> String {
+    let millis = time.as_millis();
+    let separator = match format {
+        CaptionFormat::Srt => ',',
+        CaptionFormat::WebVtt => '.',
+    };
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Text where each character is written a second after the previous one
+    fn timed(text: &str) -> (Vec<char>, Vec<Duration>) {
+        let chars: Vec<char> = text.chars().collect();
+        let times = (0..chars.len() as u64).map(Duration::from_secs).collect();
+        (chars, times)
+    }
+
+    #[test]
+    fn test_cues() {
+        let (text, times) = timed(" Hi. Yes\nno");
+        let secs = Duration::from_secs;
+        assert_eq!(
+            cues(&text, &times),
+            vec![
+                Cue {
+                    start: secs(1),
+                    end: secs(5),
+                    text: "Hi.".to_string()
+                },
+                Cue {
+                    start: secs(5),
+                    end: secs(9),
+                    text: "Yes".to_string()
+                },
+                Cue {
+                    start: secs(9),
+                    end: secs(13),
+                    text: "no".to_string()
+                },
+            ]
+        );
+        assert_eq!(cues(&[], &[]), vec![]);
+    }
+
+    #[test]
+    fn test_long_cues() {
+        let (text, times) = timed(&"word ".repeat(30));
+        let cues = cues(&text, &times);
+        assert_eq!(cues.len(), 2);
+        assert!(cues[0].text.len() <= MAX_CUE_CHARS);
+    }
+
+    #[test]
+    fn test_write_captions() {
+        let (text, times) = timed("Hi. Bye");
+        assert_eq!(
+            write_captions(CaptionFormat::Srt, &text, &times),
+            "1\n00:00:00,000 --> 00:00:04,000\nHi.\n\n2\n00:00:04,000 --> 00:00:09,000\nBye\n\n"
+        );
+        assert_eq!(
+            write_captions(CaptionFormat::WebVtt, &text, &times),
+            "WEBVTT\n\n00:00:00.000 --> 00:00:04.000\nHi.\n\n00:00:04.000 --> 00:00:09.000\nBye\n\n"
+        );
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let time = Duration::from_millis(3_723_456);
+        assert_eq!(timestamp(CaptionFormat::Srt, time), "01:02:03,456");
+        assert_eq!(timestamp(CaptionFormat::WebVtt, time), "01:02:03.456");
+    }
+}
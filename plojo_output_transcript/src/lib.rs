@@ -0,0 +1,200 @@
+//! Writes a live transcript file that is corrected in place.
+//!
+//! When text is deleted, only the end of the file is rewritten, so the file always has the same
+//! text that was written on screen. Key presses are applied to a copy of the document, so keys
+//! like backspace and return change the transcript too.
+
+use plojo_core::{Command, Controller};
+use plojo_output_buffer::BufferController;
+use std::{
+    error::Error,
+    fs::{File, OpenOptions},
+    io::{self, Seek, SeekFrom, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+mod captions;
+
+pub use captions::CaptionFormat;
+
+pub struct TranscriptController {
+    transcript: TailFile,
+    captions: Option<(CaptionFormat, TailFile)>,
+    // the document that the commands edit, which can have its cursor before the end
+    document: BufferController,
+    text: Vec<char>,
+    // when each character in the text was written, from when the transcript started
+    times: Vec<Duration>,
+    start_time: Instant,
+}
+
+impl TranscriptController {
+    /// Start a new transcript at the path, replacing any file that is already there
+    pub fn create(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            transcript: TailFile::create(path)?,
+            captions: None,
+            document: BufferController::default(),
+            text: vec![],
+            times: vec![],
+            start_time: Instant::now(),
+        })
+    }
+
+    /// Also write the transcript as timed captions. The format is from the file extension
+    /// (`.srt` or `.vtt`)
+    pub fn with_captions(mut self, path: &Path) -> Result<Self, Box<dyn Error>> {
+        let format = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(CaptionFormat::from_extension)
+            .ok_or_else(|| format!("captions file {:?} must end in .srt or .vtt", path))?;
+        self.captions = Some((format, TailFile::create(path)?));
+        Ok(self)
+    }
+
+    fn replace(&mut self, backspace_num: usize, add_text: &str) {
+        let kept = self.text.len().saturating_sub(backspace_num);
+        self.text.truncate(kept);
+        self.times.truncate(kept);
+        let now = self.start_time.elapsed();
+        for c in add_text.chars() {
+            self.text.push(c);
+            self.times.push(now);
+        }
+    }
+
+    fn write_files(&mut self) -> io::Result<()> {
+        let text: String = self.text.iter().collect();
+        self.transcript.update(&text)?;
+        if let Some((format, ref mut file)) = self.captions {
+            file.update(&captions::write_captions(format, &self.text, &self.times))?;
+        }
+        Ok(())
+    }
+}
+
+impl Controller for TranscriptController {
+    fn dispatch(&mut self, command: Command) {
+        // only changes to the text are written to the transcript
+        if let Some((deleted, added)) = self.document.apply(command) {
+            self.replace(deleted, &added);
+            if let Err(e) = self.write_files() {
+                eprintln!("[WARN] Could not write transcript: {}", e);
+            }
+        }
+    }
+}
+
+/// A file that is rewritten from the first byte that changed
+struct TailFile {
+    file: File,
+    // the current contents of the file
+    written: String,
+}
+
+impl TailFile {
+    fn create(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        Ok(Self {
+            file,
+            written: String::new(),
+        })
+    }
+
+    /// Change the file contents by truncating it to the part that is the same and writing the rest
+    fn update(&mut self, contents: &str) -> io::Result<()> {
+        let same = common_prefix_len(&self.written, contents);
+        if same == self.written.len() && same == contents.len() {
+            return Ok(());
+        }
+        if same < self.written.len() {
+            self.file.set_len(same as u64)?;
+        }
+        self.file.seek(SeekFrom::Start(same as u64))?;
+        self.file.write_all(&contents.as_bytes()[same..])?;
+        self.file.flush()?;
+
+        self.written.truncate(same);
+        self.written.push_str(&contents[same..]);
+        Ok(())
+    }
+}
+
+/// The number of bytes at the start that are the same in both strings, which is always at a
+/// character boundary
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|&((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((i, _), _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plojo_core::{Key, SpecialKey};
+    use std::fs;
+
+    #[test]
+    fn test_common_prefix_len() {
+        assert_eq!(common_prefix_len("hello", "help"), 3);
+        assert_eq!(common_prefix_len("café", "cafe"), 3);
+        assert_eq!(common_prefix_len("abc", "abcdef"), 3);
+        assert_eq!(common_prefix_len("", "abc"), 0);
+    }
+
+    #[test]
+    fn test_corrections() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transcript.txt");
+        let captions_path = dir.path().join("captions.vtt");
+        let mut controller = TranscriptController::create(&path)
+            .unwrap()
+            .with_captions(&captions_path)
+            .unwrap();
+
+        controller.dispatch(Command::Replace(0, " Hello wörld".to_string()));
+        assert_eq!(fs::read_to_string(&path).unwrap(), " Hello wörld");
+        controller.dispatch(Command::Replace(4, "orld.".to_string()));
+        controller.dispatch(Command::Replace(0, " Bye".to_string()));
+        assert_eq!(fs::read_to_string(&path).unwrap(), " Hello world. Bye");
+
+        let captions = fs::read_to_string(&captions_path).unwrap();
+        assert!(captions.starts_with("WEBVTT\n\n00:00:00.000 --> "));
+        assert!(captions.contains("\nHello world.\n\n"));
+        assert!(captions.ends_with("\nBye\n\n"));
+    }
+
+    #[test]
+    fn test_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transcript.txt");
+        let mut controller = TranscriptController::create(&path).unwrap();
+        let key = |key| Command::Keys(Key::Special(key), vec![]);
+
+        controller.dispatch(Command::Replace(0, "Hello world".to_string()));
+        controller.dispatch(key(SpecialKey::Backspace));
+        controller.dispatch(key(SpecialKey::Return));
+        controller.dispatch(Command::Replace(0, "Bye".to_string()));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Hello worl\nBye");
+        // text can be added before the end
+        controller.dispatch(key(SpecialKey::Home));
+        controller.dispatch(Command::Replace(0, "Good".to_string()));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Hello worl\nGoodBye");
+    }
+
+    #[test]
+    fn test_captions_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let controller = TranscriptController::create(&dir.path().join("transcript.txt"))
+            .unwrap()
+            .with_captions(&dir.path().join("captions.txt"));
+        assert!(controller.is_err());
+    }
+}
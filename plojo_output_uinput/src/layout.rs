@@ -0,0 +1,132 @@
+//! Finds the keys that type each character in an xkb keyboard layout.
+//!
+//! All keycodes here are Linux evdev keycodes (the xkb keycode is 8 more).
+use plojo_core::{evdev_codes::special_key_code, SpecialKey};
+use std::{collections::HashMap, error::Error, ffi::CString, ptr};
+use xkbcommon_dl::{
+    xkb_context, xkb_context_flags, xkb_key_direction, xkb_keymap, xkb_keymap_compile_flags,
+    xkb_rule_names, xkb_state, xkbcommon_option,
+};
+
+// difference between xkb and evdev keycodes
+const XKB_OFFSET: u32 = 8;
+// highest evdev keycode that can be in a keymap
+const MAX_CODE: u16 = 247;
+
+const LEFT_SHIFT: u16 = 42;
+// the key for AltGr (ISO_Level3_Shift) in most layouts that have it
+const RIGHT_ALT: u16 = 100;
+
+// modifier keys to try for each key, in the order they are preferred
+const MODIFIER_COMBOS: [&[u16]; 4] = [&[], &[LEFT_SHIFT], &[RIGHT_ALT], &[LEFT_SHIFT, RIGHT_ALT]];
+
+/// The keys for each character in a keyboard layout
+#[derive(Debug, Default)]
+pub(crate) struct Layout {
+    // keycode of each character and the modifier keys that must be held down to type it
+    chars: HashMap<char, (u16, &'static [u16])>,
+}
+
+impl Layout {
+    /// Compile the xkb layout, like "us" or "de", with an optional variant, like "dvorak"
+    pub(crate) fn from_names(layout: &str, variant: &str) -> Result<Self, Box<dyn Error>> {
+        let xkb = xkbcommon_option().ok_or("unable to load libxkbcommon")?;
+        let layout = CString::new(layout)?;
+        let variant = CString::new(variant)?;
+        let names = xkb_rule_names {
+            rules: ptr::null(),
+            model: ptr::null(),
+            layout: layout.as_ptr(),
+            variant: variant.as_ptr(),
+            options: ptr::null(),
+        };
+
+        let mut chars = HashMap::new();
+        // Safety: each object is checked for null after it is created and only freed at the end
+        unsafe {
+            let context: *mut xkb_context =
+                (xkb.xkb_context_new)(xkb_context_flags::XKB_CONTEXT_NO_FLAGS);
+            if context.is_null() {
+                return Err("unable to create xkb context".into());
+            }
+            let keymap: *mut xkb_keymap = (xkb.xkb_keymap_new_from_names)(
+                context,
+                &names,
+                xkb_keymap_compile_flags::XKB_KEYMAP_COMPILE_NO_FLAGS,
+            );
+            (xkb.xkb_context_unref)(context);
+            if keymap.is_null() {
+                return Err(format!("unable to compile xkb layout {:?}", layout).into());
+            }
+
+            let max_code =
+                (((xkb.xkb_keymap_max_keycode)(keymap) - XKB_OFFSET) as u16).min(MAX_CODE);
+            for &modifiers in MODIFIER_COMBOS.iter() {
+                let state: *mut xkb_state = (xkb.xkb_state_new)(keymap);
+                if state.is_null() {
+                    continue;
+                }
+                for &m in modifiers {
+                    (xkb.xkb_state_update_key)(
+                        state,
+                        m as u32 + XKB_OFFSET,
+                        xkb_key_direction::XKB_KEY_DOWN,
+                    );
+                }
+                for code in 1..=max_code {
+                    let c = (xkb.xkb_state_key_get_utf32)(state, code as u32 + XKB_OFFSET);
+                    if let Some(c) = std::char::from_u32(c).filter(|c| !c.is_control()) {
+                        // prefer the fewest modifiers and then the lowest keycode
+                        chars.entry(c).or_insert((code, modifiers));
+                    }
+                }
+                (xkb.xkb_state_unref)(state);
+            }
+            (xkb.xkb_keymap_unref)(keymap);
+        }
+
+        Ok(Self { chars })
+    }
+
+    /// Find the keycode and the modifier keys to type a character
+    pub(crate) fn char_key(&self, c: char) -> Option<(u16, &'static [u16])> {
+        match c {
+            '\n' => Some((special_key_code(&SpecialKey::Return), &[])),
+            '\t' => Some((special_key_code(&SpecialKey::Tab), &[])),
+            _ => self.chars.get(&c).copied(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_us_layout() {
+        let layout = Layout::from_names("us", "").unwrap();
+        assert_eq!(layout.char_key('c'), Some((46, &[][..])));
+        assert_eq!(layout.char_key('C'), Some((46, &[LEFT_SHIFT][..])));
+        assert_eq!(layout.char_key('1'), Some((2, &[][..])));
+        assert_eq!(layout.char_key(' '), Some((57, &[][..])));
+        assert_eq!(layout.char_key('\n'), Some((28, &[][..])));
+        assert_eq!(layout.char_key('é'), None);
+    }
+
+    #[test]
+    fn test_other_layouts() {
+        let layout = Layout::from_names("de", "").unwrap();
+        // z and y are swapped
+        assert_eq!(layout.char_key('z'), Some((21, &[][..])));
+        assert_eq!(layout.char_key('ä'), Some((40, &[][..])));
+        assert_eq!(layout.char_key('@'), Some((16, &[RIGHT_ALT][..])));
+
+        let layout = Layout::from_names("us", "dvorak").unwrap();
+        assert_eq!(layout.char_key('s'), Some((39, &[][..])));
+    }
+
+    #[test]
+    fn test_unknown_layout() {
+        assert!(Layout::from_names("not a layout", "").is_err());
+    }
+}
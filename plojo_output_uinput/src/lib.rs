@@ -0,0 +1,193 @@
+//! Dispatch commands with a Linux uinput virtual keyboard.
+//!
+//! The virtual keyboard sends key events at the kernel level, so it works in the console and
+//! under any display server. Writing to `/dev/uinput` usually requires root or membership in the
+//! `input` group.
+
+use evdev::{uinput::VirtualDevice, AttributeSet, EventType, InputEvent, KeyCode};
+use layout::Layout;
+use plojo_core::{evdev_codes::special_key_code, Command, Controller, Key, Modifier, SpecialKey};
+use std::{error::Error, process::Command as ProcessCommand, thread, time::Duration};
+
+mod layout;
+
+// How long a key is held down
+const KEY_HOLD_DELAY: u64 = 2;
+// Delay between successive backspaces for corrections
+const BACKSPACE_DELAY: u64 = 2;
+// Delay between successive letters for typing normal text
+const TYPE_DELAY: u64 = 5;
+// Delay for holding down each modifier key
+const MODIFIER_DELAY: u64 = 2;
+// How long to wait after creating the device for the system to start reading from it
+const DEVICE_SETUP_DELAY: u64 = 300;
+
+const KEY_FN: u16 = 464;
+
+pub struct UinputController {
+    device: VirtualDevice,
+    layout: Layout,
+}
+
+impl UinputController {
+    /// Create the virtual keyboard that types with the xkb layout (ex: "us", "de") and variant
+    /// (ex: "dvorak" or empty for the default)
+    pub fn create(layout: &str, variant: &str) -> Result<Self, Box<dyn Error>> {
+        let layout = Layout::from_names(layout, variant)?;
+
+        let mut keys = AttributeSet::<KeyCode>::new();
+        for code in 1..=255 {
+            keys.insert(KeyCode(code));
+        }
+        keys.insert(KeyCode(KEY_FN));
+        let device = VirtualDevice::builder()?
+            .name("plojo virtual keyboard")
+            .with_keys(&keys)?
+            .build()?;
+        thread::sleep(Duration::from_millis(DEVICE_SETUP_DELAY));
+
+        Ok(Self { device, layout })
+    }
+
+    fn send_key(&mut self, code: u16, pressed: bool) -> Result<(), Box<dyn Error>> {
+        let event = InputEvent::new(EventType::KEY.0, code, pressed as i32);
+        self.device.emit(&[event])?;
+        Ok(())
+    }
+
+    /// Press and release a key while holding down the modifier keys
+    fn tap_key(&mut self, code: u16, modifiers: &[u16]) -> Result<(), Box<dyn Error>> {
+        for &m in modifiers {
+            self.send_key(m, true)?;
+            thread::sleep(Duration::from_millis(MODIFIER_DELAY));
+        }
+        self.send_key(code, true)?;
+        thread::sleep(Duration::from_millis(KEY_HOLD_DELAY));
+        self.send_key(code, false)?;
+        for &m in modifiers.iter().rev() {
+            self.send_key(m, false)?;
+        }
+        Ok(())
+    }
+
+    fn try_dispatch(&mut self, command: Command) -> Result<(), Box<dyn Error>> {
+        match command {
+            Command::Replace(backspace_num, add_text) => {
+                for _ in 0..backspace_num {
+                    self.tap_key(special_key_code(&SpecialKey::Backspace), &[])?;
+                    thread::sleep(Duration::from_millis(BACKSPACE_DELAY));
+                }
+
+                for c in add_text.chars() {
+                    match self.layout.char_key(c) {
+                        Some((code, modifiers)) => self.tap_key(code, modifiers)?,
+                        None => eprintln!("[WARN] {:?} is not in the keyboard layout", c),
+                    }
+                    thread::sleep(Duration::from_millis(TYPE_DELAY));
+                }
+            }
+            Command::PrintHello => {
+                println!("Hello!");
+            }
+            Command::NoOp | Command::Pending(_) => {}
+            Command::Keys(key, modifiers) => match key_combo(&self.layout, &key, &modifiers) {
+                Some((code, modifiers)) => self.tap_key(code, &modifiers)?,
+                None => eprintln!("[WARN] {:?} is not in the keyboard layout", key),
+            },
+            // raw codes are evdev keycodes
+            Command::Raw(code) => self.tap_key(code, &[])?,
+            Command::Shell(cmd, args) => dispatch_shell(cmd, args),
+            Command::Wait(millis) => thread::sleep(Duration::from_millis(millis)),
+            command @ (Command::KeySequence(_) | Command::Unicode(_) | Command::MoveCursor(..)) => {
+                for command in command.simplify() {
+                    self.try_dispatch(command)?;
+                }
+            }
+            Command::TranslatorCommand(_) => panic!("cannot handle translator command"),
+        }
+        Ok(())
+    }
+}
+
+impl Controller for UinputController {
+    fn dispatch(&mut self, command: Command) {
+        if let Err(e) = self.try_dispatch(command) {
+            eprintln!("[WARN] Could not dispatch command with uinput: {}", e);
+        }
+    }
+}
+
+/// The keycode and modifier keys to press for a key combo, or None if the character is not in the
+/// layout. A character without modifiers is typed with the modifiers it needs in the layout (ex:
+/// shift for `!`). With modifiers, only the given modifiers are held, so that shortcuts like
+/// control+z are not changed
+fn key_combo(layout: &Layout, key: &Key, modifiers: &[Modifier]) -> Option<(u16, Vec<u16>)> {
+    let modifiers: Vec<u16> = modifiers.iter().map(modifier_code).collect();
+    match key {
+        Key::Special(special_key) => Some((special_key_code(special_key), modifiers)),
+        Key::Layout(c) => {
+            let (code, layout_modifiers) = layout.char_key(*c)?;
+            if modifiers.is_empty() {
+                Some((code, layout_modifiers.to_vec()))
+            } else {
+                Some((code, modifiers))
+            }
+        }
+    }
+}
+
+/// The evdev keycode of the left modifier key
+fn modifier_code(modifier: &Modifier) -> u16 {
+    match modifier {
+        Modifier::Alt | Modifier::Option => 56,
+        Modifier::Control => 29,
+        Modifier::Meta => 125,
+        Modifier::Shift => 42,
+        Modifier::Fn => KEY_FN,
+    }
+}
+
+fn dispatch_shell(cmd: String, args: Vec<String>) {
+    let result = ProcessCommand::new(cmd).args(args).spawn();
+    match result {
+        Ok(_) => {}
+        Err(e) => eprintln!("[WARN] Could not execute shell command: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_combo() {
+        let layout = Layout::from_names("us", "").unwrap();
+        // shift is held to type the character
+        assert_eq!(
+            key_combo(&layout, &Key::Layout('!'), &[]),
+            Some((2, vec![42]))
+        );
+        // but not when other modifiers are given
+        assert_eq!(
+            key_combo(&layout, &Key::Layout('Z'), &[Modifier::Control]),
+            Some((44, vec![29]))
+        );
+        assert_eq!(
+            key_combo(&layout, &Key::Special(SpecialKey::Tab), &[Modifier::Alt]),
+            Some((15, vec![56]))
+        );
+        assert_eq!(key_combo(&layout, &Key::Layout('é'), &[]), None);
+    }
+
+    #[test]
+    #[ignore = "needs write access to /dev/uinput"]
+    fn test_dispatch() {
+        let mut controller = UinputController::create("us", "").unwrap();
+        controller
+            .try_dispatch(Command::Replace(1, "Hi!\n".to_string()))
+            .unwrap();
+        controller
+            .try_dispatch(Command::Keys(Key::Layout('z'), vec![Modifier::Control]))
+            .unwrap();
+    }
+}
@@ -0,0 +1,306 @@
+//! Generates the xkb keymap that is uploaded to the virtual keyboard.
+//!
+//! All keycodes here are Linux evdev keycodes (the xkb keycode is 8 more). The keymap has a US
+//! layout along with the special keys and modifiers. Characters that are not in the layout are
+//! given one of the unused keycodes, which changes the keymap so it has to be uploaded again.
+use plojo_core::{evdev_codes, Modifier, SpecialKey};
+
+// xkb modifier masks for the real modifiers in their standard order
+pub(crate) const SHIFT_MASK: u32 = 1;
+pub(crate) const CONTROL_MASK: u32 = 1 << 2;
+pub(crate) const ALT_MASK: u32 = 1 << 3;
+pub(crate) const SUPER_MASK: u32 = 1 << 6;
+
+// US layout keys: (keycode, character, character with shift)
+const LAYOUT: [(u32, char, char); 47] = [
+    (2, '1', '!'),
+    (3, '2', '@'),
+    (4, '3', '#'),
+    (5, '4', '$'),
+    (6, '5', '%'),
+    (7, '6', '^'),
+    (8, '7', '&'),
+    (9, '8', '*'),
+    (10, '9', '('),
+    (11, '0', ')'),
+    (12, '-', '_'),
+    (13, '=', '+'),
+    (16, 'q', 'Q'),
+    (17, 'w', 'W'),
+    (18, 'e', 'E'),
+    (19, 'r', 'R'),
+    (20, 't', 'T'),
+    (21, 'y', 'Y'),
+    (22, 'u', 'U'),
+    (23, 'i', 'I'),
+    (24, 'o', 'O'),
+    (25, 'p', 'P'),
+    (26, '[', '{'),
+    (27, ']', '}'),
+    (30, 'a', 'A'),
+    (31, 's', 'S'),
+    (32, 'd', 'D'),
+    (33, 'f', 'F'),
+    (34, 'g', 'G'),
+    (35, 'h', 'H'),
+    (36, 'j', 'J'),
+    (37, 'k', 'K'),
+    (38, 'l', 'L'),
+    (39, ';', ':'),
+    (40, '\'', '"'),
+    (41, '`', '~'),
+    (43, '\\', '|'),
+    (44, 'z', 'Z'),
+    (45, 'x', 'X'),
+    (46, 'c', 'C'),
+    (47, 'v', 'V'),
+    (48, 'b', 'B'),
+    (49, 'n', 'N'),
+    (50, 'm', 'M'),
+    (51, ',', '<'),
+    (52, '.', '>'),
+    (53, '/', '?'),
+];
+
+// keys that are not characters: (keycode, keysym name)
+const SPECIAL_KEYS: [(u32, &str); 72] = [
+    (1, "Escape"),
+    (14, "BackSpace"),
+    (15, "Tab"),
+    (28, "Return"),
+    (29, "Control_L"),
+    (42, "Shift_L"),
+    (55, "KP_Multiply"),
+    (56, "Alt_L"),
+    (57, "space"),
+    (58, "Caps_Lock"),
+    (59, "F1"),
+    (60, "F2"),
+    (61, "F3"),
+    (62, "F4"),
+    (63, "F5"),
+    (64, "F6"),
+    (65, "F7"),
+    (66, "F8"),
+    (67, "F9"),
+    (68, "F10"),
+    (69, "Num_Lock"),
+    (70, "Scroll_Lock"),
+    (71, "KP_7"),
+    (72, "KP_8"),
+    (73, "KP_9"),
+    (74, "KP_Subtract"),
+    (75, "KP_4"),
+    (76, "KP_5"),
+    (77, "KP_6"),
+    (78, "KP_Add"),
+    (79, "KP_1"),
+    (80, "KP_2"),
+    (81, "KP_3"),
+    (82, "KP_0"),
+    (83, "KP_Decimal"),
+    (87, "F11"),
+    (88, "F12"),
+    (96, "KP_Enter"),
+    (98, "KP_Divide"),
+    (99, "Print"),
+    (102, "Home"),
+    (103, "Up"),
+    (104, "Prior"),
+    (105, "Left"),
+    (106, "Right"),
+    (107, "End"),
+    (108, "Down"),
+    (109, "Next"),
+    (110, "Insert"),
+    (111, "Delete"),
+    (113, "XF86AudioMute"),
+    (114, "XF86AudioLowerVolume"),
+    (115, "XF86AudioRaiseVolume"),
+    (119, "Pause"),
+    (125, "Super_L"),
+    (127, "Menu"),
+    (163, "XF86AudioNext"),
+    (164, "XF86AudioPlay"),
+    (165, "XF86AudioPrev"),
+    (166, "XF86AudioStop"),
+    (183, "F13"),
+    (184, "F14"),
+    (185, "F15"),
+    (186, "F16"),
+    (187, "F17"),
+    (188, "F18"),
+    (189, "F19"),
+    (190, "F20"),
+    (191, "F21"),
+    (192, "F22"),
+    (193, "F23"),
+    (194, "F24"),
+];
+
+// modifier keys and the modifier they set
+const MODIFIER_KEYS: [(u32, &str); 5] = [
+    (42, "Shift"),
+    (58, "Lock"),
+    (29, "Control"),
+    (56, "Mod1"),
+    (125, "Mod4"),
+];
+
+// unused keycodes that other characters can be assigned to, after the last special key
+const FIRST_EXTRA_CODE: u32 = 195;
+const LAST_EXTRA_CODE: u32 = 247;
+
+/// Keymap for the virtual keyboard
+#[derive(Debug, Default)]
+pub(crate) struct Keymap {
+    // characters that are not in the layout. The index is the offset from the first extra code
+    extra_chars: Vec<char>,
+}
+
+impl Keymap {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Find the keycode and modifier mask to type a character. If the character is not in the
+    /// keymap yet, it is added and the last value is true to show that the keymap changed
+    pub(crate) fn char_key(&mut self, c: char) -> (u32, u32, bool) {
+        match c {
+            ' ' => return (special_key_code(&SpecialKey::Space), 0, false),
+            '\n' => return (special_key_code(&SpecialKey::Return), 0, false),
+            '\t' => return (special_key_code(&SpecialKey::Tab), 0, false),
+            _ => {}
+        }
+        for &(code, lower, upper) in LAYOUT.iter() {
+            if c == lower {
+                return (code, 0, false);
+            } else if c == upper {
+                return (code, SHIFT_MASK, false);
+            }
+        }
+
+        if let Some(i) = self.extra_chars.iter().position(|&e| e == c) {
+            return (FIRST_EXTRA_CODE + i as u32, 0, false);
+        }
+        if self.extra_chars.len() as u32 > LAST_EXTRA_CODE - FIRST_EXTRA_CODE {
+            // out of keycodes, so start over since the old characters were already typed
+            self.extra_chars.clear();
+        }
+        self.extra_chars.push(c);
+        (
+            FIRST_EXTRA_CODE + self.extra_chars.len() as u32 - 1,
+            0,
+            true,
+        )
+    }
+
+    /// Write the keymap in the xkb text format
+    pub(crate) fn to_xkb_string(&self) -> String {
+        let mut keys: Vec<(u32, String)> = vec![];
+        for &(code, lower, upper) in LAYOUT.iter() {
+            keys.push((code, format!("{}, {}", keysym(lower), keysym(upper))));
+        }
+        for &(code, name) in SPECIAL_KEYS.iter() {
+            keys.push((code, name.to_string()));
+        }
+        for (i, &c) in self.extra_chars.iter().enumerate() {
+            keys.push((FIRST_EXTRA_CODE + i as u32, keysym(c)));
+        }
+
+        let mut keycodes = String::new();
+        let mut symbols = String::new();
+        for (code, syms) in keys {
+            keycodes.push_str(&format!("        <K{}> = {};\n", code, code + 8));
+            symbols.push_str(&format!("        key <K{}> {{ [ {} ] }};\n", code, syms));
+        }
+        for &(code, modifier) in MODIFIER_KEYS.iter() {
+            symbols.push_str(&format!(
+                "        modifier_map {} {{ <K{}> }};\n",
+                modifier, code
+            ));
+        }
+
+        let mut xkb = String::from("xkb_keymap {\n");
+        xkb.push_str("    xkb_keycodes \"plojo\" {\n");
+        xkb.push_str("        minimum = 8;\n        maximum = 255;\n");
+        xkb.push_str(&keycodes);
+        xkb.push_str("    };\n");
+        xkb.push_str("    xkb_types \"plojo\" { include \"complete\" };\n");
+        xkb.push_str("    xkb_compatibility \"plojo\" { include \"complete\" };\n");
+        xkb.push_str("    xkb_symbols \"plojo\" {\n");
+        xkb.push_str(&symbols);
+        xkb.push_str("    };\n};\n");
+        xkb
+    }
+}
+
+/// The keysym name for a character
+fn keysym(c: char) -> String {
+    format!("U{:04X}", c as u32)
+}
+
+/// The keycode of a special key
+pub(crate) fn special_key_code(key: &SpecialKey) -> u32 {
+    u32::from(evdev_codes::special_key_code(key))
+}
+
+/// The modifier mask for a modifier key. The function key cannot be sent as a modifier
+pub(crate) fn modifier_mask(modifier: &Modifier) -> Option<u32> {
+    match modifier {
+        Modifier::Alt | Modifier::Option => Some(ALT_MASK),
+        Modifier::Control => Some(CONTROL_MASK),
+        Modifier::Meta => Some(SUPER_MASK),
+        Modifier::Shift => Some(SHIFT_MASK),
+        Modifier::Fn => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_char_key() {
+        let mut keymap = Keymap::new();
+        assert_eq!(keymap.char_key('c'), (46, 0, false));
+        assert_eq!(keymap.char_key('C'), (46, SHIFT_MASK, false));
+        assert_eq!(keymap.char_key(' '), (57, 0, false));
+        assert_eq!(keymap.char_key('é'), (FIRST_EXTRA_CODE, 0, true));
+        assert_eq!(keymap.char_key('—'), (FIRST_EXTRA_CODE + 1, 0, true));
+        assert_eq!(keymap.char_key('é'), (FIRST_EXTRA_CODE, 0, false));
+    }
+
+    #[test]
+    fn test_char_key_out_of_codes() {
+        let mut keymap = Keymap::new();
+        let extra_codes = LAST_EXTRA_CODE - FIRST_EXTRA_CODE + 1;
+        for i in 0..extra_codes {
+            let c = std::char::from_u32(0x4e00 + i).unwrap();
+            assert_eq!(keymap.char_key(c), (FIRST_EXTRA_CODE + i, 0, true));
+        }
+        // the codes are reused once they run out
+        assert_eq!(keymap.char_key('é'), (FIRST_EXTRA_CODE, 0, true));
+        assert_eq!(keymap.extra_chars, vec!['é']);
+    }
+
+    #[test]
+    fn test_to_xkb_string() {
+        let mut keymap = Keymap::new();
+        keymap.char_key('é');
+        let xkb = keymap.to_xkb_string();
+        assert!(xkb.contains("<K46> = 54;"));
+        assert!(xkb.contains("key <K46> { [ U0063, U0043 ] };"));
+        assert!(xkb.contains("key <K14> { [ BackSpace ] };"));
+        assert!(xkb.contains("key <K195> { [ U00E9 ] };"));
+        assert!(xkb.contains("key <K164> { [ XF86AudioPlay ] };"));
+        assert!(xkb.contains("modifier_map Control { <K29> };"));
+    }
+
+    #[test]
+    fn test_modifier_mask() {
+        assert_eq!(modifier_mask(&Modifier::Control), Some(CONTROL_MASK));
+        assert_eq!(modifier_mask(&Modifier::Option), Some(ALT_MASK));
+        assert_eq!(modifier_mask(&Modifier::Fn), None);
+    }
+}
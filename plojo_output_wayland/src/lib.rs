@@ -1,98 +1,417 @@
-use plojo_core::{Command, Controller};
+use keymap::Keymap;
+use plojo_core::{Command, Controller, Key, OutputEvent};
 use protocol::zwp_input_method_v2::client::zwp_input_method_manager_v2::ZwpInputMethodManagerV2;
 use protocol::zwp_input_method_v2::client::zwp_input_method_v2::Event as ImEvent;
 use protocol::zwp_input_method_v2::client::zwp_input_method_v2::ZwpInputMethodV2;
+use protocol::zwp_text_input_v3::client::zwp_text_input_v3::{ContentHint, ContentPurpose};
 use protocol::zwp_virtual_keyboard_v1::client::zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1;
 use protocol::zwp_virtual_keyboard_v1::client::zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1;
-use std::process::Command as ProcessCommand;
+use std::{
+    error::Error,
+    io::{self, Seek, SeekFrom, Write},
+    os::unix::io::AsRawFd,
+    process::Command as ProcessCommand,
+    thread,
+    time::{Duration, Instant},
+};
+use surrounding::SurroundingText;
 use wayland_client::{protocol::wl_seat::WlSeat, Display, EventQueue, GlobalManager, Main};
 
 #[macro_use]
 extern crate bitflags;
 
+mod keymap;
 mod protocol;
+mod surrounding;
+
+// How long a key is held down
+const KEY_HOLD_DELAY: u64 = 2;
+// Delay between successive backspaces for corrections
+const BACKSPACE_DELAY: u64 = 2;
+// Delay between successive letters for typing normal text
+const TYPE_DELAY: u64 = 5;
+// Delay for holding down the modifier keys before pressing the key
+const MODIFIER_DELAY: u64 = 2;
+
+// values from the wayland protocol
+const KEYMAP_FORMAT_XKB_V1: u32 = 1;
+const KEY_STATE_RELEASED: u32 = 0;
+const KEY_STATE_PRESSED: u32 = 1;
 
 pub struct WaylandController {
     display: Display,
     queue: Option<EventQueue>,
-    seat: Main<WlSeat>,
     virtual_keyboard: Main<ZwpVirtualKeyboardV1>,
-    input_method: Main<ZwpInputMethodV2>,
+    // text is sent through the input method when it is available and active. Otherwise, it is
+    // typed with the virtual keyboard
+    input_method: Option<Main<ZwpInputMethodV2>>,
+    input_method_active: bool,
+    // activation is only applied on the next done event
+    pending_active: bool,
+    // whether the input method was activated or deactivated before the next done event
+    pending_focus_change: bool,
+    // whether the focused text field is for passwords or other sensitive data
+    sensitive: bool,
+    pending_sensitive: bool,
+    // text before the cursor, for converting deleted characters to bytes
+    surrounding_text: SurroundingText,
+    // surrounding text (with the cursor) to apply on the next done event
+    pending_surrounding_text: Option<(String, u32)>,
+    // events for the translator that have not been polled yet
+    events: Vec<OutputEvent>,
+    // whether to show pending text as preedit text instead of committing it right away
+    use_preedit: bool,
+    // the preedit text that is shown but not committed
+    preedit: String,
     serial: u32,
+    keymap: Keymap,
+    // key events need a timestamp in milliseconds, which is measured from here
+    start_time: Instant,
 }
 
-impl Controller for WaylandController {
-    fn new(_disable_scan_keymap: bool) -> Self {
-        let display = Display::connect_to_env().unwrap();
+impl WaylandController {
+    /// Show text that could still change as preedit text (usually underlined) and only commit it
+    /// once it is final. This needs the translator to output `Command::Pending`
+    pub fn with_preedit(mut self, use_preedit: bool) -> Self {
+        self.use_preedit = use_preedit;
+        self
+    }
+
+    /// Make the replacement in the preedit text, deleting committed text only if the replacement
+    /// goes past the start of the preedit text
+    fn replace_preedit(&mut self, input_method: &ZwpInputMethodV2, backspaces: usize, text: &str) {
+        let preedit_len = self.preedit.chars().count();
+        if backspaces <= preedit_len {
+            let kept: String = self
+                .preedit
+                .chars()
+                .take(preedit_len - backspaces)
+                .collect();
+            self.preedit = kept;
+        } else {
+            // the preedit text is removed before deleting, so only count the committed text
+            let deleted_bytes = self.surrounding_text.replace(backspaces - preedit_len, "");
+            input_method.delete_surrounding_text(deleted_bytes as u32, 0);
+            self.preedit.clear();
+        }
+        self.preedit.push_str(text);
+
+        let cursor = self.preedit.len() as i32;
+        input_method.set_preedit_string(self.preedit.clone(), cursor, cursor);
+        input_method.commit(self.serial);
+    }
+
+    /// Commit all of the preedit text except for the pending characters at the end
+    fn commit_preedit(&mut self, input_method: &ZwpInputMethodV2, pending: usize) {
+        let preedit_len = self.preedit.chars().count();
+        let split = self
+            .preedit
+            .char_indices()
+            .nth(preedit_len.saturating_sub(pending))
+            .map_or(self.preedit.len(), |(i, _)| i);
+        let pending_text = self.preedit.split_off(split);
+        if !self.preedit.is_empty() {
+            self.surrounding_text.replace(0, &self.preedit);
+            input_method.commit_string(std::mem::take(&mut self.preedit));
+        }
+        self.preedit = pending_text;
+
+        let cursor = self.preedit.len() as i32;
+        input_method.set_preedit_string(self.preedit.clone(), cursor, cursor);
+        input_method.commit(self.serial);
+    }
+
+    /// Upload the keymap to the virtual keyboard. This must be done before sending any keys and
+    /// every time the keymap changes
+    fn upload_keymap(&self) -> io::Result<()> {
+        let xkb = self.keymap.to_xkb_string();
+        let mut file = tempfile::tempfile()?;
+        // the keymap must be null terminated
+        file.write_all(xkb.as_bytes())?;
+        file.write_all(&[0])?;
+        file.seek(SeekFrom::Start(0))?;
+        self.virtual_keyboard
+            .keymap(KEYMAP_FORMAT_XKB_V1, file.as_raw_fd(), xkb.len() as u32 + 1);
+        // the file must stay open until the compositor receives it
+        self.display.flush()
+    }
+
+    /// Upload the keymap after a key was added to it. If that fails, the key types whatever it
+    /// was before
+    fn reupload_keymap(&self) {
+        if let Err(e) = self.upload_keymap() {
+            eprintln!("[WARN] Could not upload the Wayland keymap: {}", e);
+        }
+    }
+
+    /// Send the requests to the compositor
+    fn flush(&self) {
+        if let Err(e) = self.display.flush() {
+            eprintln!(
+                "[WARN] Could not send requests to the Wayland compositor: {}",
+                e
+            );
+        }
+    }
+
+    fn time(&self) -> u32 {
+        self.start_time.elapsed().as_millis() as u32
+    }
+
+    /// Press and release a key while holding down the modifiers
+    fn tap_key(&self, keycode: u32, modifiers: u32) {
+        if modifiers != 0 {
+            self.virtual_keyboard.modifiers(modifiers, 0, 0, 0);
+            self.flush();
+            thread::sleep(Duration::from_millis(MODIFIER_DELAY));
+        }
+
+        self.virtual_keyboard
+            .key(self.time(), keycode, KEY_STATE_PRESSED);
+        self.flush();
+        thread::sleep(Duration::from_millis(KEY_HOLD_DELAY));
+        self.virtual_keyboard
+            .key(self.time(), keycode, KEY_STATE_RELEASED);
+
+        if modifiers != 0 {
+            self.virtual_keyboard.modifiers(0, 0, 0, 0);
+        }
+        self.flush();
+    }
+
+    /// Type the text with the virtual keyboard after pressing backspace
+    fn type_text(&mut self, backspaces: usize, text: &str) {
+        let backspace = keymap::special_key_code(&plojo_core::SpecialKey::Backspace);
+        for _ in 0..backspaces {
+            self.tap_key(backspace, 0);
+            thread::sleep(Duration::from_millis(BACKSPACE_DELAY));
+        }
+
+        for c in text.chars() {
+            let (keycode, mask, changed) = self.keymap.char_key(c);
+            if changed {
+                self.reupload_keymap();
+            }
+            self.tap_key(keycode, mask);
+            thread::sleep(Duration::from_millis(TYPE_DELAY));
+        }
+    }
+
+    /// Read and handle the events from the compositor without blocking
+    fn read_events(&mut self) {
+        if let Some(guard) = self.queue.as_ref().unwrap().prepare_read() {
+            if let Err(e) = guard.read_events() {
+                if e.kind() != std::io::ErrorKind::WouldBlock {
+                    eprintln!("[WARN] Could not read Wayland events: {}", e);
+                }
+            }
+        }
+        let mut queue = self.queue.take().unwrap();
+        if let Err(e) = queue.dispatch_pending(self, |_, _, _| {}) {
+            eprintln!("[WARN] Could not handle Wayland events: {}", e);
+        }
+        self.queue = Some(queue);
+    }
+
+    fn handle_input_method_event(&mut self, event: ImEvent) {
+        self.pending_sensitive = sensitive_after(&event, self.pending_sensitive);
+        match event {
+            ImEvent::Activate => {
+                self.pending_active = true;
+                self.pending_focus_change = true;
+            }
+            ImEvent::Deactivate => {
+                self.pending_active = false;
+                self.pending_focus_change = true;
+            }
+            ImEvent::SurroundingText { text, cursor, .. } => {
+                self.pending_surrounding_text = Some((text, cursor))
+            }
+            ImEvent::TextChangeCause { .. } | ImEvent::ContentType { .. } => {}
+            ImEvent::Done => {
+                if self.pending_focus_change && self.input_method_active && !self.preedit.is_empty()
+                {
+                    // the compositor removes the preedit text from the old text field, so commit
+                    // it while the old state still applies instead of losing the words
+                    if let Some(input_method) = self.input_method.clone() {
+                        self.commit_preedit(&input_method, 0);
+                        if let Err(e) = self.display.flush() {
+                            eprintln!("[WARN] Could not commit the preedit text: {}", e);
+                        }
+                    }
+                }
+                self.input_method_active = self.pending_active;
+                if self.pending_focus_change {
+                    // the text before the cursor is unknown until the text field sends it
+                    self.pending_focus_change = false;
+                    self.surrounding_text = SurroundingText::default();
+                    self.preedit.clear();
+                    self.events.push(OutputEvent::FocusChanged);
+                }
+                let sensitive = self.input_method_active && self.pending_sensitive;
+                if sensitive != self.sensitive {
+                    self.sensitive = sensitive;
+                    self.events.push(OutputEvent::SensitiveField(sensitive));
+                }
+                if let Some((text, cursor)) = self.pending_surrounding_text.take() {
+                    self.surrounding_text.set(&text, cursor as usize);
+                    self.events.push(OutputEvent::SurroundingText(
+                        self.surrounding_text.before_cursor().to_string(),
+                    ));
+                }
+                self.serial = self.serial.wrapping_add(1);
+            }
+            ImEvent::Unavailable => {
+                // another input method is running, so it cannot be used at all
+                eprintln!("[WARN] Input method unavailable, typing with the virtual keyboard");
+                if let Some(input_method) = self.input_method.take() {
+                    input_method.destroy();
+                }
+                self.input_method_active = false;
+            }
+        }
+    }
+
+    /// Press a key with some modifier keys
+    fn key_combo(&mut self, key: Key, modifiers: Vec<plojo_core::Modifier>) {
+        let (keycode, mut mask) = match key {
+            Key::Special(special_key) => (keymap::special_key_code(&special_key), 0),
+            Key::Layout(c) => {
+                let (keycode, mask, changed) = self.keymap.char_key(c);
+                if changed {
+                    self.reupload_keymap();
+                }
+                (keycode, mask)
+            }
+        };
+        for m in &modifiers {
+            match keymap::modifier_mask(m) {
+                Some(m) => mask |= m,
+                None => eprintln!("[WARN] Cannot press {:?} on Wayland", m),
+            }
+        }
+        self.tap_key(keycode, mask);
+    }
+}
+
+/// Whether the focused text field is sensitive (for passwords, etc.) after an input method event,
+/// given whether it was sensitive before
+fn sensitive_after(event: &ImEvent, sensitive: bool) -> bool {
+    match event {
+        // the content type is reset when activated
+        ImEvent::Activate => false,
+        ImEvent::ContentType { hint, purpose } => {
+            hint.contains(ContentHint::SensitiveData)
+                || hint.contains(ContentHint::HiddenText)
+                || matches!(purpose, ContentPurpose::Password | ContentPurpose::Pin)
+        }
+        _ => sensitive,
+    }
+}
+
+impl WaylandController {
+    /// Connect to the Wayland compositor from the `WAYLAND_DISPLAY` environment variable
+    pub fn connect() -> Result<Self, Box<dyn Error>> {
+        let display = Display::connect_to_env()?;
         let mut queue = display.create_event_queue();
         let attached = (*display).clone().attach(queue.token());
         let manager = GlobalManager::new(&attached);
-        queue.sync_roundtrip(&mut (), |_, _, _| {}).unwrap();
-        let seat = manager.instantiate_exact::<WlSeat>(1).unwrap();
+        queue.sync_roundtrip(&mut (), |_, _, _| {})?;
+        let seat = manager
+            .instantiate_exact::<WlSeat>(1)
+            .map_err(|_| "the compositor does not have a seat")?;
         let virtual_keyboard_manager = manager
             .instantiate_exact::<ZwpVirtualKeyboardManagerV1>(1)
-            .unwrap();
-        let input_method_manager = manager
-            .instantiate_exact::<ZwpInputMethodManagerV2>(1)
-            .unwrap();
+            .map_err(|_| "the compositor does not support the virtual keyboard protocol")?;
         let virtual_keyboard = virtual_keyboard_manager.create_virtual_keyboard(&seat);
-        let input_method = input_method_manager.get_input_method(&seat);
-        input_method.quick_assign(|input_method, event, mut data| {
-            let data: &mut Self = data.get().unwrap();
-            match event {
-                ImEvent::Activate => {}
-                ImEvent::Deactivate => {}
-                ImEvent::SurroundingText {
-                    text,
-                    cursor,
-                    anchor,
-                } => {}
-                ImEvent::TextChangeCause { cause } => {}
-                ImEvent::ContentType { hint, purpose } => {}
-                ImEvent::Done => data.serial = data.serial.wrapping_add(1),
-                ImEvent::Unavailable => panic!("input method unavailable"),
+        // the input method is optional since the virtual keyboard can type text instead
+        let input_method = match manager.instantiate_exact::<ZwpInputMethodManagerV2>(1) {
+            Ok(input_method_manager) => {
+                let input_method = input_method_manager.get_input_method(&seat);
+                input_method.quick_assign(|_, event, mut data| {
+                    let data: &mut Self = data.get().unwrap();
+                    data.handle_input_method_event(event);
+                });
+                Some(input_method)
+            }
+            Err(_) => {
+                eprintln!("[WARN] The compositor does not support the input method protocol");
+                None
             }
-        });
-        Self {
+        };
+        let controller = Self {
             display,
             queue: Some(queue),
-            seat,
             virtual_keyboard,
             input_method,
+            input_method_active: false,
+            pending_active: false,
+            pending_focus_change: false,
+            sensitive: false,
+            pending_sensitive: false,
+            surrounding_text: SurroundingText::default(),
+            pending_surrounding_text: None,
+            events: vec![],
+            use_preedit: false,
+            preedit: String::new(),
             serial: 0,
-        }
+            keymap: Keymap::new(),
+            start_time: Instant::now(),
+        };
+        controller.upload_keymap()?;
+        Ok(controller)
     }
+}
 
+impl Controller for WaylandController {
     fn dispatch(&mut self, command: Command) {
-        if let Some(guard) = self.queue.as_ref().unwrap().prepare_read() {
-            if let Err(e) = guard.read_events() {
-                if e.kind() != std::io::ErrorKind::WouldBlock {
-                    panic!(e);
-                }
-            }
-        }
-        {
-            let mut queue = self.queue.take().unwrap();
-            queue.dispatch_pending(self, |_, _, _| {}).unwrap();
-            self.queue = Some(queue);
-        }
+        self.read_events();
         match command {
-            Command::Replace(backspaces, text) => {
-                self.input_method
-                    .delete_surrounding_text(backspaces as u32, 0);
-                self.input_method.commit_string(text);
-                self.input_method.commit(self.serial);
-            }
+            Command::Replace(backspaces, text) => match self.input_method.clone() {
+                Some(input_method) if self.input_method_active && self.use_preedit => {
+                    self.replace_preedit(&input_method, backspaces, &text)
+                }
+                Some(input_method) if self.input_method_active => {
+                    // the input method deletes by bytes instead of characters
+                    let deleted_bytes = self.surrounding_text.replace(backspaces, &text);
+                    input_method.delete_surrounding_text(deleted_bytes as u32, 0);
+                    input_method.commit_string(text);
+                    input_method.commit(self.serial);
+                }
+                _ => {
+                    self.surrounding_text.replace(backspaces, &text);
+                    self.type_text(backspaces, &text);
+                }
+            },
             Command::PrintHello => {
                 println!("Hello!");
             }
             Command::NoOp => {}
-            Command::Keys(_, _) => {}
-            Command::Raw(_) => {}
+            Command::Keys(key, modifiers) => self.key_combo(key, modifiers),
+            // raw codes are evdev keycodes in the keymap of the virtual keyboard
+            Command::Raw(code) => self.tap_key(code as u32, 0),
             Command::Shell(cmd, args) => dispatch_shell(cmd, args),
+            Command::Wait(millis) => thread::sleep(Duration::from_millis(millis)),
+            command @ (Command::KeySequence(_) | Command::Unicode(_) | Command::MoveCursor(..)) => {
+                for command in command.simplify() {
+                    self.dispatch(command);
+                }
+            }
             Command::TranslatorCommand(_) => panic!("cannot handle translator command"),
+            Command::Pending(pending) => match self.input_method.clone() {
+                Some(input_method) if self.input_method_active && self.use_preedit => {
+                    self.commit_preedit(&input_method, pending)
+                }
+                // typed text cannot be shown as pending
+                _ => {}
+            },
         }
-        self.display.flush().unwrap();
+        self.flush();
+    }
+
+    fn poll_events(&mut self) -> Vec<OutputEvent> {
+        self.read_events();
+        std::mem::take(&mut self.events)
     }
 }
 
@@ -103,3 +422,41 @@ fn dispatch_shell(cmd: String, args: Vec<String>) {
         Err(e) => eprintln!("[WARN] Could not execute shell command: {}", e),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content_type(hint: ContentHint, purpose: ContentPurpose) -> ImEvent {
+        ImEvent::ContentType { hint, purpose }
+    }
+
+    #[test]
+    fn test_sensitive_after() {
+        let normal = content_type(ContentHint::None, ContentPurpose::Normal);
+        assert!(!sensitive_after(&normal, false));
+        assert!(!sensitive_after(&normal, true));
+        for purpose in [ContentPurpose::Password, ContentPurpose::Pin] {
+            assert!(sensitive_after(
+                &content_type(ContentHint::None, purpose),
+                false
+            ));
+        }
+        for hint in [ContentHint::SensitiveData, ContentHint::HiddenText] {
+            assert!(sensitive_after(
+                &content_type(hint, ContentPurpose::Normal),
+                false
+            ));
+        }
+        let hints = ContentHint::Spellcheck | ContentHint::HiddenText;
+        assert!(sensitive_after(
+            &content_type(hints, ContentPurpose::Normal),
+            false
+        ));
+
+        // activating a new text field resets it, and other events keep it
+        assert!(!sensitive_after(&ImEvent::Activate, true));
+        assert!(sensitive_after(&ImEvent::Deactivate, true));
+        assert!(sensitive_after(&ImEvent::Done, true));
+    }
+}
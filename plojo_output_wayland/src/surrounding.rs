@@ -0,0 +1,97 @@
+//! Keeps track of the text before the cursor.
+//!
+//! The input method deletes text by bytes, but the translator counts backspaces in characters, so
+//! the text that is being deleted is needed to find how many bytes to delete.
+
+// How much text to keep (the input method protocol limits surrounding text to 4000 bytes)
+const MAX_LEN: usize = 4000;
+
+#[derive(Debug, Default)]
+pub(crate) struct SurroundingText {
+    before_cursor: String,
+}
+
+impl SurroundingText {
+    /// Use the surrounding text sent by the text field. The cursor is a byte index into the text
+    pub(crate) fn set(&mut self, text: &str, cursor: usize) {
+        if let Some(before_cursor) = text.get(..cursor) {
+            self.before_cursor = before_cursor.to_string();
+        }
+    }
+
+    pub(crate) fn before_cursor(&self) -> &str {
+        &self.before_cursor
+    }
+
+    /// Delete the characters before the cursor and add the text. Returns the number of bytes
+    /// that were deleted
+    pub(crate) fn replace(&mut self, backspaces: usize, text: &str) -> usize {
+        let bytes = char_count_to_bytes(&self.before_cursor, backspaces);
+        let kept = self.before_cursor.len().saturating_sub(bytes);
+        self.before_cursor.truncate(kept);
+        self.before_cursor.push_str(text);
+
+        if self.before_cursor.len() > MAX_LEN {
+            let mut start = self.before_cursor.len() - MAX_LEN;
+            while !self.before_cursor.is_char_boundary(start) {
+                start += 1;
+            }
+            self.before_cursor.drain(..start);
+        }
+
+        bytes
+    }
+}
+
+/// The number of bytes in the last few characters of the text. Characters from before the start
+/// of the text are unknown, so they are assumed to take up a single byte
+fn char_count_to_bytes(text: &str, chars: usize) -> usize {
+    let known: usize = text.chars().rev().take(chars).map(char::len_utf8).sum();
+    let unknown = chars.saturating_sub(text.chars().count());
+    known + unknown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_char_count_to_bytes() {
+        assert_eq!(char_count_to_bytes("hello", 2), 2);
+        assert_eq!(char_count_to_bytes("wait—what", 5), 7);
+        assert_eq!(char_count_to_bytes(" café", 1), 2);
+        assert_eq!(char_count_to_bytes("é", 3), 4);
+        assert_eq!(char_count_to_bytes("", 2), 2);
+    }
+
+    #[test]
+    fn test_replace_multibyte() {
+        let mut surrounding = SurroundingText::default();
+        assert_eq!(surrounding.replace(0, " naïve—"), 0);
+        // correcting the em dash and the word before it
+        assert_eq!(surrounding.replace(6, "naive -"), 9);
+        assert_eq!(surrounding.before_cursor, " naive -");
+        assert_eq!(surrounding.replace(2, ""), 2);
+        assert_eq!(surrounding.before_cursor, " naive");
+    }
+
+    #[test]
+    fn test_set() {
+        let mut surrounding = SurroundingText::default();
+        surrounding.set("résumé text", 8);
+        assert_eq!(surrounding.before_cursor, "résumé");
+        assert_eq!(surrounding.replace(2, "e"), 3);
+        assert_eq!(surrounding.before_cursor, "résue");
+        // the cursor is not on a character boundary, so it is ignored
+        surrounding.set("é", 1);
+        assert_eq!(surrounding.before_cursor, "résue");
+    }
+
+    #[test]
+    fn test_replace_max_len() {
+        let mut surrounding = SurroundingText::default();
+        surrounding.replace(0, &"é".repeat(MAX_LEN));
+        assert!(surrounding.before_cursor.len() <= MAX_LEN);
+        assert_eq!(surrounding.replace(1, ""), 2);
+    }
+}
@@ -0,0 +1,138 @@
+//! Converts characters and keys into X11 keysyms
+use plojo_core::{Modifier, SpecialKey};
+use x11rb::protocol::xproto::Keysym;
+
+pub(crate) const SHIFT_L: Keysym = 0xffe1;
+const CONTROL_L: Keysym = 0xffe3;
+const ALT_L: Keysym = 0xffe9;
+const SUPER_L: Keysym = 0xffeb;
+
+const RETURN: Keysym = 0xff0d;
+const TAB: Keysym = 0xff09;
+const BACKSPACE: Keysym = 0xff08;
+
+// keysyms for unicode characters outside of Latin-1 are offset by this
+const UNICODE_OFFSET: Keysym = 0x0100_0000;
+
+/// Find the keysym that types the character
+pub(crate) fn char_to_keysym(c: char) -> Keysym {
+    match c {
+        '\n' => RETURN,
+        '\t' => TAB,
+        '\u{8}' => BACKSPACE,
+        // Latin-1 characters have the same keysym as their code point
+        ' '..='~' | '\u{a0}'..='\u{ff}' => c as Keysym,
+        _ => UNICODE_OFFSET + c as Keysym,
+    }
+}
+
+pub(crate) fn special_key_to_keysym(key: &SpecialKey) -> Keysym {
+    match key {
+        SpecialKey::Backspace => BACKSPACE,
+        SpecialKey::CapsLock => 0xffe5,
+        SpecialKey::Delete => 0xffff,
+        SpecialKey::DownArrow => 0xff54,
+        SpecialKey::End => 0xff57,
+        SpecialKey::Escape => 0xff1b,
+        SpecialKey::F1 => 0xffbe,
+        SpecialKey::F2 => 0xffbf,
+        SpecialKey::F3 => 0xffc0,
+        SpecialKey::F4 => 0xffc1,
+        SpecialKey::F5 => 0xffc2,
+        SpecialKey::F6 => 0xffc3,
+        SpecialKey::F7 => 0xffc4,
+        SpecialKey::F8 => 0xffc5,
+        SpecialKey::F9 => 0xffc6,
+        SpecialKey::F10 => 0xffc7,
+        SpecialKey::F11 => 0xffc8,
+        SpecialKey::F12 => 0xffc9,
+        SpecialKey::F13 => 0xffca,
+        SpecialKey::F14 => 0xffcb,
+        SpecialKey::F15 => 0xffcc,
+        SpecialKey::F16 => 0xffcd,
+        SpecialKey::F17 => 0xffce,
+        SpecialKey::F18 => 0xffcf,
+        SpecialKey::F19 => 0xffd0,
+        SpecialKey::F20 => 0xffd1,
+        SpecialKey::F21 => 0xffd2,
+        SpecialKey::F22 => 0xffd3,
+        SpecialKey::F23 => 0xffd4,
+        SpecialKey::F24 => 0xffd5,
+        SpecialKey::Home => 0xff50,
+        SpecialKey::Insert => 0xff63,
+        SpecialKey::LeftArrow => 0xff51,
+        SpecialKey::MediaNextTrack => 0x1008_ff17,
+        SpecialKey::MediaPlayPause => 0x1008_ff14,
+        SpecialKey::MediaPreviousTrack => 0x1008_ff16,
+        SpecialKey::MediaStop => 0x1008_ff15,
+        SpecialKey::Menu => 0xff67,
+        SpecialKey::NumLock => 0xff7f,
+        SpecialKey::Numpad0 => 0xffb0,
+        SpecialKey::Numpad1 => 0xffb1,
+        SpecialKey::Numpad2 => 0xffb2,
+        SpecialKey::Numpad3 => 0xffb3,
+        SpecialKey::Numpad4 => 0xffb4,
+        SpecialKey::Numpad5 => 0xffb5,
+        SpecialKey::Numpad6 => 0xffb6,
+        SpecialKey::Numpad7 => 0xffb7,
+        SpecialKey::Numpad8 => 0xffb8,
+        SpecialKey::Numpad9 => 0xffb9,
+        SpecialKey::NumpadAdd => 0xffab,
+        SpecialKey::NumpadDecimal => 0xffae,
+        SpecialKey::NumpadDivide => 0xffaf,
+        SpecialKey::NumpadEnter => 0xff8d,
+        SpecialKey::NumpadMultiply => 0xffaa,
+        SpecialKey::NumpadSubtract => 0xffad,
+        SpecialKey::PageDown => 0xff56,
+        SpecialKey::PageUp => 0xff55,
+        SpecialKey::Pause => 0xff13,
+        SpecialKey::PrintScreen => 0xff61,
+        SpecialKey::Return => RETURN,
+        SpecialKey::RightArrow => 0xff53,
+        SpecialKey::ScrollLock => 0xff14,
+        SpecialKey::Space => 0x20,
+        SpecialKey::Tab => TAB,
+        SpecialKey::UpArrow => 0xff52,
+        SpecialKey::VolumeDown => 0x1008_ff11,
+        SpecialKey::VolumeMute => 0x1008_ff12,
+        SpecialKey::VolumeUp => 0x1008_ff13,
+    }
+}
+
+/// The keysym of a modifier key. There is no keysym for the function key
+pub(crate) fn modifier_to_keysym(modifier: &Modifier) -> Option<Keysym> {
+    match modifier {
+        Modifier::Alt | Modifier::Option => Some(ALT_L),
+        Modifier::Control => Some(CONTROL_L),
+        Modifier::Meta => Some(SUPER_L),
+        Modifier::Shift => Some(SHIFT_L),
+        Modifier::Fn => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_char_to_keysym() {
+        assert_eq!(char_to_keysym('a'), 0x61);
+        assert_eq!(char_to_keysym('~'), 0x7e);
+        assert_eq!(char_to_keysym('é'), 0xe9);
+        assert_eq!(char_to_keysym('—'), 0x0100_2014);
+        assert_eq!(char_to_keysym('\n'), RETURN);
+    }
+
+    #[test]
+    fn test_special_key_to_keysym() {
+        assert_eq!(special_key_to_keysym(&SpecialKey::F24), 0xffd5);
+        assert_eq!(special_key_to_keysym(&SpecialKey::Numpad9), 0xffb9);
+        assert_eq!(special_key_to_keysym(&SpecialKey::VolumeMute), 0x1008_ff12);
+    }
+
+    #[test]
+    fn test_modifier_to_keysym() {
+        assert_eq!(modifier_to_keysym(&Modifier::Option), Some(ALT_L));
+        assert_eq!(modifier_to_keysym(&Modifier::Fn), None);
+    }
+}
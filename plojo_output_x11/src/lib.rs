@@ -0,0 +1,361 @@
+//! Dispatch commands on X11 with the XTest extension.
+
+use plojo_core::{Command, Controller, Key, OutputEvent, SpecialKey};
+use std::{
+    borrow::Cow, collections::HashMap, error::Error, process::Command as ProcessCommand, thread,
+    time::Duration,
+};
+use x11rb::{
+    connection::Connection,
+    protocol::{
+        xproto::{
+            AtomEnum, ConnectionExt as _, InputFocus, Keycode, Keysym, Window, KEY_PRESS_EVENT,
+            KEY_RELEASE_EVENT,
+        },
+        xtest::ConnectionExt as _,
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+    CURRENT_TIME, NONE, NO_SYMBOL,
+};
+
+mod keysym;
+
+// How long a key is held down
+const KEY_HOLD_DELAY: u64 = 2;
+// Delay between successive backspaces for corrections
+const BACKSPACE_DELAY: u64 = 2;
+// Delay between successive letters for typing normal text
+const TYPE_DELAY: u64 = 5;
+// Delay for holding down each modifier key
+const MODIFIER_DELAY: u64 = 2;
+// How long to wait before undoing a temporary key remapping, so that applications read the key
+// press with the remapped keymap
+const REMAP_DELAY: u64 = 20;
+
+pub struct X11Controller {
+    conn: RustConnection,
+    root: Window,
+    // Stores the keymap if keymap scanning is disabled (keymap is only scanned at the beginning)
+    // If it's not disabled, then the keymap is scanned for every command (to see if it changed)
+    keymap: Option<Keymap>,
+    // the window that had focus the last time events were polled
+    focus: Option<Window>,
+}
+
+/// Keycodes for the keysyms in the keyboard layout
+#[derive(Debug, Clone, PartialEq)]
+struct Keymap {
+    // keycode of each keysym and whether shift must be held down to type it
+    keycodes: HashMap<Keysym, (Keycode, bool)>,
+    // a keycode without any keysyms, which is used to temporarily type keysyms that are not in
+    // the layout
+    spare_keycode: Option<Keycode>,
+}
+
+impl Keymap {
+    /// Build the keymap from the keyboard mapping, where each keycode starting from the minimum
+    /// keycode has `keysyms_per_keycode` keysyms
+    fn from_mapping(min_keycode: Keycode, keysyms_per_keycode: u8, keysyms: &[Keysym]) -> Self {
+        let mut keycodes = HashMap::new();
+        let mut spare_keycode = None;
+        for (i, syms) in keysyms.chunks(keysyms_per_keycode as usize).enumerate() {
+            let keycode = min_keycode + i as Keycode;
+            if syms.iter().all(|&s| s == NO_SYMBOL) {
+                spare_keycode = Some(keycode);
+                continue;
+            }
+            // only the first two keysyms are typed without any modifiers other than shift
+            for (shift, &sym) in syms.iter().take(2).enumerate() {
+                if sym != NO_SYMBOL {
+                    // prefer the lowest keycode for a keysym
+                    keycodes.entry(sym).or_insert((keycode, shift == 1));
+                }
+            }
+        }
+
+        Self {
+            keycodes,
+            spare_keycode,
+        }
+    }
+
+    fn keycode(&self, keysym: Keysym) -> Option<Keycode> {
+        self.keycodes.get(&keysym).map(|&(keycode, _)| keycode)
+    }
+}
+
+impl X11Controller {
+    /// Connect to the X server from the `DISPLAY` environment variable
+    pub fn connect(disable_scan_keymap: bool) -> Result<Self, Box<dyn Error>> {
+        let (conn, screen) = x11rb::connect(None)?;
+        let root = conn.setup().roots[screen].root;
+        // make sure that the XTest extension is available
+        conn.xtest_get_version(2, 2)?.reply()?;
+
+        let mut controller = Self {
+            conn,
+            root,
+            keymap: None,
+            focus: None,
+        };
+        if disable_scan_keymap {
+            // to disable keymap scanning, scan it only once at the beginning
+            controller.keymap = Some(controller.scan_keymap()?);
+        }
+        Ok(controller)
+    }
+
+    fn scan_keymap(&self) -> Result<Keymap, Box<dyn Error>> {
+        let setup = self.conn.setup();
+        let count = setup.max_keycode - setup.min_keycode + 1;
+        let mapping = self
+            .conn
+            .get_keyboard_mapping(setup.min_keycode, count)?
+            .reply()?;
+        Ok(Keymap::from_mapping(
+            setup.min_keycode,
+            mapping.keysyms_per_keycode,
+            &mapping.keysyms,
+        ))
+    }
+
+    /// Use the keymap that was scanned at the beginning or scan it again
+    fn current_keymap(&self) -> Result<Cow<'_, Keymap>, Box<dyn Error>> {
+        match self.keymap {
+            Some(ref keymap) => Ok(Cow::Borrowed(keymap)),
+            None => Ok(Cow::Owned(self.scan_keymap()?)),
+        }
+    }
+
+    fn fake_key(&self, keycode: Keycode, pressed: bool) -> Result<(), Box<dyn Error>> {
+        let event = if pressed {
+            KEY_PRESS_EVENT
+        } else {
+            KEY_RELEASE_EVENT
+        };
+        self.conn
+            .xtest_fake_input(event, keycode, CURRENT_TIME, self.root, 0, 0, 0)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Press and release a key while holding down the modifier keys
+    fn tap_key(&self, keycode: Keycode, modifiers: &[Keycode]) -> Result<(), Box<dyn Error>> {
+        for &m in modifiers {
+            self.fake_key(m, true)?;
+            thread::sleep(Duration::from_millis(MODIFIER_DELAY));
+        }
+        self.fake_key(keycode, true)?;
+        thread::sleep(Duration::from_millis(KEY_HOLD_DELAY));
+        self.fake_key(keycode, false)?;
+        for &m in modifiers.iter().rev() {
+            self.fake_key(m, false)?;
+        }
+        Ok(())
+    }
+
+    /// Press the key for the keysym, temporarily mapping it to the spare keycode if it is not in
+    /// the keyboard layout
+    fn tap_keysym(
+        &self,
+        keymap: &Keymap,
+        keysym: Keysym,
+        modifiers: &[Keycode],
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(&(keycode, shift)) = keymap.keycodes.get(&keysym) {
+            let mut modifiers = modifiers.to_vec();
+            if shift {
+                if let Some(shift) = keymap.keycode(keysym::SHIFT_L) {
+                    modifiers.push(shift);
+                }
+            }
+            self.tap_key(keycode, &modifiers)
+        } else if let Some(spare) = keymap.spare_keycode {
+            self.conn
+                .change_keyboard_mapping(1, spare, 2, &[keysym, keysym])?;
+            self.conn.sync()?;
+            self.tap_key(spare, modifiers)?;
+            thread::sleep(Duration::from_millis(REMAP_DELAY));
+            self.conn
+                .change_keyboard_mapping(1, spare, 2, &[NO_SYMBOL, NO_SYMBOL])?;
+            self.conn.sync()?;
+            Ok(())
+        } else {
+            Err(format!("no keycode is available to type keysym {:#x}", keysym).into())
+        }
+    }
+
+    fn try_dispatch(&mut self, command: Command) -> Result<(), Box<dyn Error>> {
+        match command {
+            Command::Replace(backspace_num, add_text) => {
+                let keymap = self.current_keymap()?;
+                let backspace = keysym::special_key_to_keysym(&SpecialKey::Backspace);
+                for _ in 0..backspace_num {
+                    self.tap_keysym(&keymap, backspace, &[])?;
+                    thread::sleep(Duration::from_millis(BACKSPACE_DELAY));
+                }
+
+                for c in add_text.chars() {
+                    self.tap_keysym(&keymap, keysym::char_to_keysym(c), &[])?;
+                    thread::sleep(Duration::from_millis(TYPE_DELAY));
+                }
+            }
+            Command::PrintHello => {
+                println!("Hello!");
+            }
+            Command::NoOp | Command::Pending(_) => {}
+            Command::Keys(key, modifiers) => {
+                let keymap = self.current_keymap()?;
+                let mut modifier_keycodes = vec![];
+                for m in &modifiers {
+                    match keysym::modifier_to_keysym(m).and_then(|k| keymap.keycode(k)) {
+                        Some(keycode) => modifier_keycodes.push(keycode),
+                        None => eprintln!("[WARN] Cannot press {:?} on X11", m),
+                    }
+                }
+                let keysym = match key {
+                    Key::Special(special_key) => keysym::special_key_to_keysym(&special_key),
+                    Key::Layout(c) => keysym::char_to_keysym(c),
+                };
+                self.tap_keysym(&keymap, keysym, &modifier_keycodes)?;
+            }
+            // raw codes are X11 keycodes
+            Command::Raw(code) => self.tap_key(code as Keycode, &[])?,
+            Command::Shell(cmd, args) => dispatch_shell(cmd, args),
+            Command::Wait(millis) => thread::sleep(Duration::from_millis(millis)),
+            command @ (Command::KeySequence(_) | Command::Unicode(_) | Command::MoveCursor(..)) => {
+                for command in command.simplify() {
+                    self.try_dispatch(command)?;
+                }
+            }
+            Command::TranslatorCommand(_) => panic!("cannot handle translator command"),
+        }
+        Ok(())
+    }
+}
+
+impl Controller for X11Controller {
+    fn dispatch(&mut self, command: Command) {
+        if let Err(e) = self.try_dispatch(command) {
+            eprintln!("[WARN] Could not dispatch command on X11: {}", e);
+        }
+    }
+
+    /// Check if another window was focused since the last poll
+    fn poll_events(&mut self) -> Vec<OutputEvent> {
+        let focus = match self.conn.get_input_focus().map(|c| c.reply()) {
+            Ok(Ok(reply)) => reply.focus,
+            _ => return vec![],
+        };
+        let changed = self.focus.is_some_and(|f| f != focus);
+        self.focus = Some(focus);
+        if changed {
+            vec![OutputEvent::FocusChanged]
+        } else {
+            vec![]
+        }
+    }
+
+    fn focused_application(&mut self) -> Option<String> {
+        match self.focused_window_class() {
+            Ok(class) => class,
+            Err(e) => {
+                eprintln!("[WARN] Could not get the focused window: {}", e);
+                None
+            }
+        }
+    }
+}
+
+impl X11Controller {
+    /// The class of the focused window (from `WM_CLASS`)
+    fn focused_window_class(&self) -> Result<Option<String>, Box<dyn Error>> {
+        let mut window = self.conn.get_input_focus()?.reply()?.focus;
+        // the focus is often a child of the application's window, which has the class
+        while window != NONE && window != u32::from(InputFocus::POINTER_ROOT) && window != self.root
+        {
+            let property = self
+                .conn
+                .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 1024)?
+                .reply()?;
+            if let Some(class) = window_class(&property.value) {
+                return Ok(Some(class));
+            }
+            window = self.conn.query_tree(window)?.reply()?.parent;
+        }
+        Ok(None)
+    }
+}
+
+/// The class from a `WM_CLASS` property, which is the instance name and then the class, each
+/// ending with a null byte
+fn window_class(property: &[u8]) -> Option<String> {
+    let class = property.split(|&b| b == 0).nth(1)?;
+    if class.is_empty() {
+        None
+    } else {
+        Some(String::from_utf8_lossy(class).into_owned())
+    }
+}
+
+fn dispatch_shell(cmd: String, args: Vec<String>) {
+    let result = ProcessCommand::new(cmd).args(args).spawn();
+    match result {
+        Ok(_) => {}
+        Err(e) => eprintln!("[WARN] Could not execute shell command: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keymap_from_mapping() {
+        let a = 0x61;
+        let upper_a = 0x41;
+        #[rustfmt::skip]
+        let keysyms = [
+            // keycode 8: a and A, with a third keysym that needs another modifier
+            a, upper_a, 0xe6,
+            // keycode 9: empty
+            NO_SYMBOL, NO_SYMBOL, NO_SYMBOL,
+            // keycode 10: a again
+            a, upper_a, NO_SYMBOL,
+            // keycode 11: shift
+            keysym::SHIFT_L, NO_SYMBOL, NO_SYMBOL,
+        ];
+        let keymap = Keymap::from_mapping(8, 3, &keysyms);
+        assert_eq!(keymap.keycodes.get(&a), Some(&(8, false)));
+        assert_eq!(keymap.keycodes.get(&upper_a), Some(&(8, true)));
+        assert_eq!(keymap.keycodes.get(&0xe6), None);
+        assert_eq!(keymap.keycode(keysym::SHIFT_L), Some(11));
+        assert_eq!(keymap.spare_keycode, Some(9));
+    }
+
+    #[test]
+    fn test_window_class() {
+        assert_eq!(window_class(b"xterm\0XTerm\0"), Some("XTerm".to_string()));
+        assert_eq!(window_class(b"xterm\0"), None);
+        assert_eq!(window_class(b""), None);
+    }
+
+    #[test]
+    #[ignore = "needs an X server with XTest (ex: run with xvfb-run)"]
+    fn test_dispatch_with_remapping() {
+        let mut controller = X11Controller::connect(true).unwrap();
+        let keymap_before = controller.scan_keymap().unwrap();
+        controller
+            .try_dispatch(Command::Replace(1, "hi—é".to_string()))
+            .unwrap();
+        controller
+            .try_dispatch(Command::Keys(
+                Key::Layout('a'),
+                vec![plojo_core::Modifier::Control],
+            ))
+            .unwrap();
+        // the temporary remapping is undone
+        assert_eq!(controller.scan_keymap().unwrap(), keymap_before);
+    }
+}
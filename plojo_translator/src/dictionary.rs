@@ -1,6 +1,6 @@
 use crate::Translation;
 use plojo_core::Stroke;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::iter::FromIterator;
 
@@ -12,6 +12,8 @@ type DictEntry = (Stroke, Translation);
 #[derive(Debug, PartialEq)]
 pub struct Dictionary {
     strokes: HashMap<Stroke, Translation>,
+    // the first strokes of every multi-stroke entry (ex: "A" and "A/B" for "A/B/C")
+    prefixes: HashSet<Stroke>,
 }
 
 impl Dictionary {
@@ -27,14 +29,12 @@ impl Dictionary {
     }
 
     fn lookup(&self, strokes: &[Stroke]) -> Option<Translation> {
-        // combine strokes with a `/` between them
-        let combined = strokes
-            .iter()
-            .map(|s| s.clone().to_raw())
-            .collect::<Vec<_>>()
-            .join("/");
-
-        self.strokes.get(&Stroke::new(&combined)).cloned()
+        self.strokes.get(&combine_strokes(strokes)).cloned()
+    }
+
+    /// Whether the strokes are the start of a longer entry in the dictionary
+    pub(super) fn is_prefix(&self, strokes: &[Stroke]) -> bool {
+        self.prefixes.contains(&combine_strokes(strokes))
     }
 
     pub(super) fn translate(&self, strokes: &[Stroke]) -> Vec<Translation> {
@@ -42,14 +42,32 @@ impl Dictionary {
     }
 }
 
+/// Combine strokes with a `/` between them
+fn combine_strokes(strokes: &[Stroke]) -> Stroke {
+    let combined = strokes
+        .iter()
+        .map(|s| s.clone().to_raw())
+        .collect::<Vec<_>>()
+        .join("/");
+    Stroke::new(&combined)
+}
+
 impl FromIterator<DictEntry> for Dictionary {
     fn from_iter<T: IntoIterator<Item = DictEntry>>(iter: T) -> Self {
         let mut hashmap: HashMap<Stroke, Translation> = HashMap::new();
+        let mut prefixes = HashSet::new();
         for (stroke, translations) in iter {
+            let raw = stroke.clone().to_raw();
+            for (i, _) in raw.match_indices('/') {
+                prefixes.insert(Stroke::new(&raw[..i]));
+            }
             hashmap.insert(stroke, translations);
         }
 
-        Dictionary { strokes: hashmap }
+        Dictionary {
+            strokes: hashmap,
+            prefixes,
+        }
     }
 }
 
@@ -80,4 +98,21 @@ mod tests {
             Translation::Text(vec![Text::Lit("something else".to_string())])
         );
     }
+
+    #[test]
+    fn dictionary_prefixes() {
+        let raw_dict = r#"
+            {
+                "H-L": "hello",
+                "A/B/C": "abc"
+            }
+        "#
+        .to_string();
+
+        let dict = Dictionary::new(vec![raw_dict]).unwrap();
+        assert!(dict.is_prefix(&[Stroke::new("A")]));
+        assert!(dict.is_prefix(&[Stroke::new("A"), Stroke::new("B")]));
+        assert!(!dict.is_prefix(&[Stroke::new("A"), Stroke::new("B"), Stroke::new("C")]));
+        assert!(!dict.is_prefix(&[Stroke::new("H-L")]));
+    }
 }
@@ -1,4 +1,7 @@
-use crate::{AttachedType, StateAction, Text, TextAction, Translation};
+use crate::{
+    AttachedType, ConditionRegex, ModeAction, NumberStyle, StateAction, Text, TextAction,
+    Translation,
+};
 use plojo_core::{Command, Stroke};
 use regex::Regex;
 use serde_json::{self, Error as JsonError, Value};
@@ -62,6 +65,39 @@ use std::{error::Error, fmt};
 /// - `{>}`: lowercase next word
 /// - `{*>}`: lowercase previous word
 ///
+/// ### Retroactive formatting of several words
+/// These take an optional number of words (`:n`) to apply to, which defaults to 1. Words are runs
+/// of letters, numbers, hyphens and underscores; anything after the last word (like punctuation)
+/// is left alone.
+/// - `{*-|:3}`: capitalize each of the previous 3 words
+/// - `{*<:2}`: uppercase the previous 2 words
+/// - `{*>:2}`: lowercase the previous 2 words
+/// - `{*CAMEL:4}`: join the previous 4 words into camelCase
+/// - `{*SNAKE:4}`: join the previous 4 words into snake_case
+/// - `{*QUOTE}`: wrap the previous word in double quotes (`{*QUOTE:2}` for 2 words)
+///
+/// Only whitespace between the words is removed when joining words.
+///
+/// ### Retroactive number formatting
+/// These reformat the number just before them, which is made up of digits with an optional
+/// decimal part (numbers written with glued number strokes count as one number). The separators
+/// for thousands and decimals can be changed in the translator options.
+/// - `{*($c)}`: currency with 2 decimal places, where the first `c` is replaced by the number.
+///   For example, `1250` becomes `$1,250.00`. Any text can surround the `c` (ex: `{*(c €)}`)
+/// - `{*NUM:GROUP}`: add thousands separators (`1250` becomes `1,250`)
+/// - `{*NUM:SHORT}`: shorten with a suffix (`1250000` becomes `1.25M`)
+/// - `{*NUM:WORDS}`: write out the number in words (`23` becomes `twenty-three`)
+/// - `{*NUM:DIGITS}`: the opposite of `{*NUM:WORDS}`, which converts the number written in words
+///   just before it into digits (`one hundred and five` becomes `105`)
+///
+/// ### Conditional text
+/// - `{=REGEX/if_match/if_not}`: outputs `if_match` if the next word starts with a match of the
+///   regex and `if_not` otherwise. For example, `{=(?i)[aeiou]/an/a}` outputs "an" before "apple"
+///   and "a" before "pear".
+///     - until the next word is written, `if_not` is used. The text is corrected once the next
+///       word is known
+///     - a `/` in the regex or text must be escaped as `\/`. The regex cannot contain brackets
+///
 /// ### Literal symbols
 /// - `{bracketleft}`: inserts a literal opening bracket (`{`)
 /// - `{bracketright}`: inserts a literal closing bracket (`}`)
@@ -69,6 +105,24 @@ use std::{error::Error, fmt};
 /// ### Canceling Formatting of Next Word
 /// - The empty text commmand (`{}`) cancels the state actions (mostly formatting actions)
 ///
+/// ### Output modes
+/// Unlike the formatting actions above, modes apply to every word after them until they are
+/// changed or reset. The mode names are case insensitive.
+/// - `{MODE:CAPS}`: uppercase (ALL CAPS) every word
+/// - `{MODE:LOWER}`: lowercase every word
+/// - `{MODE:TITLE}`: capitalize every word
+/// - `{MODE:CAMEL}`: camelCase (capitalize every word except the first and remove spaces)
+/// - `{MODE:SNAKE}`: snake_case (join words with underscores)
+/// - `{MODE:SET_SPACE:x}`: join words with `x` instead of a space (`x` can be empty)
+/// - `{MODE:RESET_CASE}`, `{MODE:RESET_SPACE}`: reset the case or the space only
+/// - `{MODE:RESET}`: reset both the case and the space
+///
+/// The first word after a mode change is still separated with a space, so only the words written
+/// in the mode are joined. Suffixes attached to a word are not treated as a new word.
+///
+/// In the object form, a mode can be set with the `mode` key (ex: `"mode": "Caps"` or
+/// `"mode": {"SetSpace": "-"}`), which is applied before `text_after`.
+///
 /// ## Differences from plover
 ///
 /// - Retrospective remove space works on the previous word, not the previous stroke
@@ -94,9 +148,15 @@ pub(super) fn load_dicts(contents: &str) -> Result<Entries, ParseError> {
                 })?;
                 let parsed: Vec<Command> = serde_json::from_value(commands.clone())?;
                 let mut texts: Option<Vec<Text>> = None;
+                if let Some(raw) = obj.get("mode") {
+                    let mode: ModeAction = serde_json::from_value(raw.clone())?;
+                    texts = Some(vec![Text::StateAction(StateAction::Mode(mode))]);
+                }
                 if let Some(raw) = obj.get("text_after") {
                     let raw_str: String = serde_json::from_value(raw.clone())?;
-                    texts = Some(parse_translation(&raw_str)?);
+                    texts
+                        .get_or_insert_with(Vec::new)
+                        .append(&mut parse_translation(&raw_str)?);
                 }
                 let suppress_space_before = if let Some(s) = obj.get("suppress_space_before") {
                     serde_json::from_value(s.clone())?
@@ -218,6 +278,15 @@ lazy_static! {
     // part of the attached_regex (which checks for attach operator)
     // checks if the content of the suffix starts with `~|`, to carry the capitalization
     static ref CARRYING_CAP: Regex = Regex::new(r"^~\|(.+)$").unwrap();
+    // 1st capturing group: name of the mode
+    // 2nd capturing group: possible argument (for setting the space)
+    static ref MODE_REGEX: Regex = Regex::new(r"^(?i:mode):([A-Za-z_]+)(?::(.*))?$").unwrap();
+    // 1st capturing group: currency template, which must contain a `c`
+    static ref RETRO_CURRENCY_REGEX: Regex = Regex::new(r"^\*\((.*c.*)\)$").unwrap();
+    // 1st capturing group: the retroactive action
+    // 2nd capturing group: possible number of words to apply the action to
+    static ref RETRO_WORDS_REGEX: Regex =
+        Regex::new(r"^\*(-\||<|>|(?i:camel|snake|quote))(?::([0-9]+))?$").unwrap();
 }
 
 /// Parses "special actions" which are in the translation surrounded by brackets
@@ -259,6 +328,33 @@ fn parse_special(t: &str) -> Result<Vec<Text>, ParseError> {
         // insert literal bracket
         "bracketleft" => Ok(vec![Text::Lit("{".to_string())]),
         "bracketright" => Ok(vec![Text::Lit("}".to_string())]),
+        // retroactive actions on several words
+        _t if RETRO_WORDS_REGEX.is_match(_t) => Ok(vec![Text::TextAction(parse_retro_words(_t)?)]),
+        // retroactive currency (the template is inside the parentheses)
+        _t if RETRO_CURRENCY_REGEX.is_match(_t) => {
+            let template = RETRO_CURRENCY_REGEX.captures(_t).unwrap()[1].to_string();
+            Ok(vec![Text::TextAction(TextAction::CurrencyPrev(template))])
+        }
+        // retroactive number formatting
+        _t if _t.to_uppercase().starts_with("*NUM:") => match _t[5..].to_uppercase().as_ref() {
+            "GROUP" => Ok(vec![Text::TextAction(TextAction::FormatNumberPrev(
+                NumberStyle::Grouped,
+            ))]),
+            "SHORT" => Ok(vec![Text::TextAction(TextAction::FormatNumberPrev(
+                NumberStyle::Short,
+            ))]),
+            "WORDS" => Ok(vec![Text::TextAction(TextAction::FormatNumberPrev(
+                NumberStyle::Words,
+            ))]),
+            "DIGITS" => Ok(vec![Text::TextAction(TextAction::WordsToNumberPrev)]),
+            _ => Err(ParseError::InvalidSpecialAction(_t.to_string())),
+        },
+        // conditional text depending on the next word
+        _t if _t.starts_with('=') => Ok(vec![parse_conditional(_t)?]),
+        // output modes
+        _t if MODE_REGEX.is_match(_t) => {
+            Ok(vec![Text::StateAction(StateAction::Mode(parse_mode(_t)?))])
+        }
         _t => {
             // check for prefix/suffix action (attach operator)
             let matched = ATTACHED_REGEX.captures(_t);
@@ -338,12 +434,92 @@ fn parse_special(t: &str) -> Result<Vec<Text>, ParseError> {
     }
 }
 
+/// Parses a retroactive action that applies to several words (ex: `*-|:3` or `*CAMEL:2`)
+///
+/// The number of words defaults to 1 if it is not given
+fn parse_retro_words(t: &str) -> Result<TextAction, ParseError> {
+    let groups = RETRO_WORDS_REGEX
+        .captures(t)
+        .ok_or_else(|| ParseError::InvalidSpecialAction(t.to_string()))?;
+    let words = match groups.get(2) {
+        Some(n) => n
+            .as_str()
+            .parse()
+            .map_err(|_| ParseError::InvalidSpecialAction(t.to_string()))?,
+        None => 1,
+    };
+    if words == 0 {
+        return Err(ParseError::InvalidSpecialAction(t.to_string()));
+    }
+
+    match groups[1].to_uppercase().as_ref() {
+        "-|" => Ok(TextAction::CapitalizePrevWords(words)),
+        "<" => Ok(TextAction::SameCasePrevWords(true, words)),
+        ">" => Ok(TextAction::SameCasePrevWords(false, words)),
+        "CAMEL" => Ok(TextAction::CamelPrevWords(words)),
+        "SNAKE" => Ok(TextAction::SnakePrevWords(words)),
+        "QUOTE" => Ok(TextAction::QuotePrevWords(words)),
+        _ => Err(ParseError::InvalidSpecialAction(t.to_string())),
+    }
+}
+
+/// Parses a conditional translation (`=REGEX/if_match/if_not`), where a `/` can be escaped as `\/`
+fn parse_conditional(t: &str) -> Result<Text, ParseError> {
+    let invalid = || ParseError::InvalidSpecialAction(t.to_string());
+
+    let mut parts = vec![String::new()];
+    // skip the leading `=` (1 byte long)
+    let mut chars = t[1..].chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'/') => {
+                chars.next();
+                parts.last_mut().ok_or_else(invalid)?.push('/');
+            }
+            '/' => parts.push(String::new()),
+            c => parts.last_mut().ok_or_else(invalid)?.push(c),
+        }
+    }
+
+    if let [regex, if_match, if_not] = parts.as_slice() {
+        Ok(Text::Conditional {
+            regex: ConditionRegex::new(regex).map_err(|_| invalid())?,
+            if_match: if_match.to_string(),
+            if_not: if_not.to_string(),
+        })
+    } else {
+        Err(invalid())
+    }
+}
+
+/// Parses a Plover output mode (ex: `MODE:CAPS` or `MODE:SET_SPACE:-`)
+fn parse_mode(t: &str) -> Result<ModeAction, ParseError> {
+    let groups = MODE_REGEX
+        .captures(t)
+        .ok_or_else(|| ParseError::InvalidSpecialAction(t.to_string()))?;
+    let argument = groups.get(2).map(|m| m.as_str());
+    match (groups[1].to_uppercase().as_ref(), argument) {
+        ("CAPS", None) => Ok(ModeAction::Caps),
+        ("LOWER", None) => Ok(ModeAction::Lower),
+        ("TITLE", None) => Ok(ModeAction::Title),
+        ("CAMEL", None) => Ok(ModeAction::Camel),
+        ("SNAKE", None) => Ok(ModeAction::Snake),
+        ("SET_SPACE", Some(space)) => Ok(ModeAction::SetSpace(space.to_string())),
+        ("RESET_CASE", None) => Ok(ModeAction::ResetCase),
+        ("RESET_SPACE", None) => Ok(ModeAction::ResetSpace),
+        ("RESET", None) => Ok(ModeAction::Reset),
+        _ => Err(ParseError::InvalidSpecialAction(t.to_string())),
+    }
+}
+
 // Parses directly as a text literal
 fn parse_as_text(t: &str) -> Text {
     Text::Lit(t.to_string())
 }
 
 #[cfg(test)]
+// entries are hashed by the pattern of their regex, which never changes
+#[allow(clippy::mutable_key_type)]
 mod tests {
     use super::*;
     use plojo_core::{Key, Modifier, SpecialKey};
@@ -539,7 +715,14 @@ mod tests {
         let contents = r#"
 {
 "UP": {"cmds": [{ "Keys": [{"Special": "UpArrow"}, []] }]},
-"TEGT": {"cmds": [{ "Keys": [{"Layout": "a"}, ["Meta"]] }]}
+"SRAUL": {"cmds": [{ "Keys": [{"Special": "VolumeUp"}, []] }]},
+"TEGT": {"cmds": [{ "Keys": [{"Layout": "a"}, ["Meta"]] }]},
+"SEUG": {"cmds": [
+    { "KeySequence": [[{"Special": "End"}, []], [{"Special": "Return"}, ["Shift"]]] },
+    { "Wait": 50 },
+    { "Unicode": 8212 },
+    { "MoveCursor": [-2, 1] }
+]}
 }
         "#;
         let parsed = load_dicts(contents).unwrap();
@@ -554,6 +737,14 @@ mod tests {
                     suppress_space_before: false,
                 },
             ),
+            (
+                Stroke::new("SRAUL"),
+                Translation::Command {
+                    cmds: vec![Command::Keys(Key::Special(SpecialKey::VolumeUp), vec![])],
+                    text_after: None,
+                    suppress_space_before: false,
+                },
+            ),
             (
                 Stroke::new("TEGT"),
                 Translation::Command {
@@ -562,6 +753,202 @@ mod tests {
                     suppress_space_before: false,
                 },
             ),
+            (
+                Stroke::new("SEUG"),
+                Translation::Command {
+                    cmds: vec![
+                        Command::KeySequence(vec![
+                            (Key::Special(SpecialKey::End), vec![]),
+                            (Key::Special(SpecialKey::Return), vec![Modifier::Shift]),
+                        ]),
+                        Command::Wait(50),
+                        Command::Unicode(0x2014),
+                        Command::MoveCursor(-2, 1),
+                    ],
+                    text_after: None,
+                    suppress_space_before: false,
+                },
+            ),
+        ];
+        let expect: HashSet<Entry> = HashSet::from_iter(expect.iter().cloned());
+
+        assert_eq!(parsed, expect);
+    }
+
+    #[test]
+    fn test_parse_modes() {
+        assert_eq!(
+            parse_translation("{MODE:CAPS}").unwrap(),
+            vec![Text::StateAction(StateAction::Mode(ModeAction::Caps))]
+        );
+        // mode names are case insensitive
+        assert_eq!(
+            parse_translation("{mode:camel}").unwrap(),
+            vec![Text::StateAction(StateAction::Mode(ModeAction::Camel))]
+        );
+        // the space argument is taken literally
+        assert_eq!(
+            parse_translation("{MODE:SET_SPACE:_X}").unwrap(),
+            vec![Text::StateAction(StateAction::Mode(ModeAction::SetSpace(
+                "_X".to_string()
+            )))]
+        );
+        assert_eq!(
+            parse_translation("{MODE:SET_SPACE:}").unwrap(),
+            vec![Text::StateAction(StateAction::Mode(ModeAction::SetSpace(
+                "".to_string()
+            )))]
+        );
+        assert_eq!(
+            parse_translation("{MODE:RESET}{^}").unwrap(),
+            vec![
+                Text::StateAction(StateAction::Mode(ModeAction::Reset)),
+                Text::Attached {
+                    text: "".to_string(),
+                    joined_next: true,
+                    joined_prev: AttachedType::ApplyOrthography,
+                    carry_capitalization: false,
+                },
+            ]
+        );
+        assert_eq!(
+            parse_translation("{MODE:SHOUT}").unwrap_err(),
+            ParseError::InvalidSpecialAction("MODE:SHOUT".to_string())
+        );
+        assert_eq!(
+            parse_translation("{MODE:CAPS:x}").unwrap_err(),
+            ParseError::InvalidSpecialAction("MODE:CAPS:x".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_retro_words() {
+        assert_eq!(
+            parse_translation("{*-|:3}").unwrap(),
+            vec![Text::TextAction(TextAction::CapitalizePrevWords(3))]
+        );
+        assert_eq!(
+            parse_translation("{*<:2}{*>:12}").unwrap(),
+            vec![
+                Text::TextAction(TextAction::SameCasePrevWords(true, 2)),
+                Text::TextAction(TextAction::SameCasePrevWords(false, 12)),
+            ]
+        );
+        assert_eq!(
+            parse_translation("{*CAMEL:4}{*snake:2}").unwrap(),
+            vec![
+                Text::TextAction(TextAction::CamelPrevWords(4)),
+                Text::TextAction(TextAction::SnakePrevWords(2)),
+            ]
+        );
+        // the number of words defaults to 1
+        assert_eq!(
+            parse_translation("{*QUOTE}").unwrap(),
+            vec![Text::TextAction(TextAction::QuotePrevWords(1))]
+        );
+        // the original single word actions are unchanged
+        assert_eq!(
+            parse_translation("{*-|}").unwrap(),
+            vec![Text::TextAction(TextAction::CapitalizePrev)]
+        );
+        assert_eq!(
+            parse_translation("{*-|:0}").unwrap_err(),
+            ParseError::InvalidSpecialAction("*-|:0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_retro_number() {
+        assert_eq!(
+            parse_translation("{*($c)}").unwrap(),
+            vec![Text::TextAction(TextAction::CurrencyPrev("$c".to_string()))]
+        );
+        assert_eq!(
+            parse_translation("{*(c CAD)}").unwrap(),
+            vec![Text::TextAction(TextAction::CurrencyPrev(
+                "c CAD".to_string()
+            ))]
+        );
+        assert_eq!(
+            parse_translation("{*NUM:GROUP}{*num:short}").unwrap(),
+            vec![
+                Text::TextAction(TextAction::FormatNumberPrev(NumberStyle::Grouped)),
+                Text::TextAction(TextAction::FormatNumberPrev(NumberStyle::Short)),
+            ]
+        );
+        assert_eq!(
+            parse_translation("{*NUM:WORDS}{*num:digits}").unwrap(),
+            vec![
+                Text::TextAction(TextAction::FormatNumberPrev(NumberStyle::Words)),
+                Text::TextAction(TextAction::WordsToNumberPrev),
+            ]
+        );
+        assert_eq!(
+            parse_translation("{*NUM:ROMAN}").unwrap_err(),
+            ParseError::InvalidSpecialAction("*NUM:ROMAN".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_conditional() {
+        assert_eq!(
+            parse_translation("{=(?i)[aeiou]/an/a}").unwrap(),
+            vec![Text::Conditional {
+                regex: ConditionRegex::new("(?i)[aeiou]").unwrap(),
+                if_match: "an".to_string(),
+                if_not: "a".to_string(),
+            }]
+        );
+        // escaped slashes and empty text
+        assert_eq!(
+            parse_translation(r"{=a\/b/and\/or/}").unwrap(),
+            vec![Text::Conditional {
+                regex: ConditionRegex::new("a/b").unwrap(),
+                if_match: "and/or".to_string(),
+                if_not: "".to_string(),
+            }]
+        );
+        assert_eq!(
+            parse_translation("{=a/b}").unwrap_err(),
+            ParseError::InvalidSpecialAction("=a/b".to_string())
+        );
+        assert_eq!(
+            parse_translation("{=(/b/c}").unwrap_err(),
+            ParseError::InvalidSpecialAction("=(/b/c".to_string())
+        );
+    }
+
+    #[test]
+    fn test_mode_object_parse_dictionary() {
+        let contents = r#"
+{
+"KA*PS": {"cmds": [], "mode": "Caps"},
+"STPH": {"cmds": [], "mode": {"SetSpace": "-"}, "text_after": "{-|}"}
+}
+        "#;
+        let parsed = load_dicts(contents).unwrap();
+        let parsed: HashSet<Entry> = HashSet::from_iter(parsed.iter().cloned());
+
+        let expect = vec![
+            (
+                Stroke::new("KA*PS"),
+                Translation::Command {
+                    cmds: vec![],
+                    text_after: Some(vec![Text::StateAction(StateAction::Mode(ModeAction::Caps))]),
+                    suppress_space_before: false,
+                },
+            ),
+            (
+                Stroke::new("STPH"),
+                Translation::Command {
+                    cmds: vec![],
+                    text_after: Some(vec![
+                        Text::StateAction(StateAction::Mode(ModeAction::SetSpace("-".to_string()))),
+                        Text::StateAction(StateAction::ForceCapitalize),
+                    ]),
+                    suppress_space_before: false,
+                },
+            ),
         ];
         let expect: HashSet<Entry> = HashSet::from_iter(expect.iter().cloned());
 
@@ -1,25 +1,33 @@
 //! Helper functions for finding the difference between 2 translations and turning that into a command.
-use crate::Translation;
+use crate::{NumberSeparators, Translation};
 use plojo_core::Command;
 use std::cmp;
 
 mod parser;
 
 use parser::parse_translation;
+pub(super) use parser::Mode;
 
 const SPACE: char = ' ';
 
 /// Finds the difference between two translations, converts them to their string representations,
 /// and diffs the strings to create a command. Has an option to insert spaces after words instead
-/// of before
+/// of before. Both translations start in the output mode from the text before them
 pub(super) fn translation_diff(
     old: &[Translation],
     new: &[Translation],
+    start_mode: &Mode,
     space_after: bool,
+    number_separators: &NumberSeparators,
 ) -> Vec<Command> {
     // ignore commands and convert old translations to text
     let old_translations: Vec<_> = old.iter().flat_map(|t| Translation::as_text(t)).collect();
-    let old_parsed = parse_translation(old_translations, space_after);
+    let old_parsed = parse_translation(
+        old_translations,
+        &mut start_mode.clone(),
+        space_after,
+        number_separators,
+    );
 
     // if added a command, return that directly
     if old.len() + 1 == new.len() {
@@ -47,12 +55,30 @@ pub(super) fn translation_diff(
 
     // ignore commands and convert old translations to text
     let new_translations: Vec<_> = new.iter().flat_map(|t| Translation::as_text(t)).collect();
-    let new_parsed = parse_translation(new_translations, space_after);
+    let new_parsed = parse_translation(
+        new_translations,
+        &mut start_mode.clone(),
+        space_after,
+        number_separators,
+    );
 
     // compare the two and return the result
     vec![text_diff(old_parsed, new_parsed)]
 }
 
+/// The output mode after the translations, given the mode before them
+pub(super) fn mode_after(
+    translations: &[Translation],
+    start_mode: &Mode,
+    space_after: bool,
+    number_separators: &NumberSeparators,
+) -> Mode {
+    let texts: Vec<_> = translations.iter().flat_map(Translation::as_text).collect();
+    let mut mode = start_mode.clone();
+    parse_translation(texts, &mut mode, space_after, number_separators);
+    mode
+}
+
 /// Compute the command necessary to make the old string into the new
 fn text_diff(old: String, new: String) -> Command {
     if old.is_empty() {
@@ -95,7 +121,7 @@ mod tests {
     use plojo_core::Stroke;
 
     fn translation_diff_space_after(old: &[Translation], new: &[Translation]) -> Vec<Command> {
-        translation_diff(old, new, false)
+        translation_diff(old, new, &Default::default(), false, &Default::default())
     }
 
     fn basic_command(cmds: Vec<Command>) -> Translation {
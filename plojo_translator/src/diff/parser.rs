@@ -1,8 +1,9 @@
-use crate::{AttachedType, StateAction, Text, TextAction};
+use crate::{AttachedType, ModeAction, NumberSeparators, StateAction, Text, TextAction};
 use orthography::apply_orthography;
 use regex::Regex;
 use std::char;
 
+mod number;
 mod orthography;
 
 lazy_static! {
@@ -23,17 +24,118 @@ struct State {
     force_same_case: Option<bool>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CaseMode {
+    Caps,
+    Lower,
+    Title,
+    Camel,
+}
+
+/// Output mode that persists across words (unlike State, which only affects the next word)
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct Mode {
+    case: Option<CaseMode>,
+    // replaces the space between words (None for a regular space)
+    space: Option<String>,
+    // number of words written since the mode was last changed
+    words: usize,
+}
+
+impl Mode {
+    /// The same mode, but as if it was just set so that it starts over with the next word
+    pub(crate) fn restarted(mut self) -> Self {
+        self.words = 0;
+        self
+    }
+
+    fn apply_action(&mut self, action: ModeAction) {
+        match action {
+            ModeAction::Caps => self.case = Some(CaseMode::Caps),
+            ModeAction::Lower => self.case = Some(CaseMode::Lower),
+            ModeAction::Title => self.case = Some(CaseMode::Title),
+            ModeAction::Camel => {
+                self.case = Some(CaseMode::Camel);
+                self.space = Some("".to_string());
+            }
+            ModeAction::Snake => self.space = Some("_".to_string()),
+            ModeAction::SetSpace(space) => self.space = Some(space),
+            ModeAction::ResetCase => self.case = None,
+            ModeAction::ResetSpace => self.space = None,
+            ModeAction::Reset => {
+                self.case = None;
+                self.space = None;
+            }
+        }
+        self.words = 0;
+    }
+
+    /// What to insert between the previous word and the next word
+    ///
+    /// The first word after a mode change is separated with a regular space so that the mode
+    /// only joins the words written while it is active
+    fn separator(&self) -> String {
+        match self.space {
+            Some(ref space) if self.words > 0 => space.clone(),
+            _ => SPACE.to_string(),
+        }
+    }
+
+    /// Applies the mode to a new word (which may itself contain spaces)
+    fn apply_word(&mut self, word: &str) -> String {
+        if word.is_empty() {
+            return String::new();
+        }
+
+        let mut result = String::new();
+        for (i, part) in word.split(SPACE).enumerate() {
+            if i > 0 {
+                result.push_str(&self.separator());
+            }
+            let part = match self.case {
+                Some(CaseMode::Caps) => part.to_uppercase(),
+                Some(CaseMode::Lower) => part.to_lowercase(),
+                Some(CaseMode::Title) => word_change_first_letter(part.to_string()),
+                Some(CaseMode::Camel) if self.words == 0 => word_lower_first_letter(part),
+                Some(CaseMode::Camel) => word_change_first_letter(part.to_string()),
+                None => part.to_string(),
+            };
+            result.push_str(&part);
+            if !part.is_empty() {
+                self.words += 1;
+            }
+        }
+        result
+    }
+
+    /// Applies the mode to text attached to the previous word (only the case is changed)
+    fn apply_attached(&self, text: &str) -> String {
+        match self.case {
+            Some(CaseMode::Caps) => text.to_uppercase(),
+            Some(CaseMode::Lower) => text.to_lowercase(),
+            _ => text.to_string(),
+        }
+    }
+}
+
 /// Converts translations into their string representation by adding spaces in between words and
 /// applying text actions. Has an option to insert spaces after words instead of before.
 ///
 /// A state of the spaces/capitalization is kept as it loops over the Texts to build the string.
-/// StateActions change that state
-pub(super) fn parse_translation(translations: Vec<Text>, space_after: bool) -> String {
+/// StateActions change that state. Output modes are kept separately because they are not reset
+/// after each word. The mode starts as the mode from the text before the translations and is
+/// changed to the mode after them
+pub(super) fn parse_translation(
+    translations: Vec<Text>,
+    mode: &mut Mode,
+    space_after: bool,
+    number_separators: &NumberSeparators,
+) -> String {
     // current state
     let mut state: State = Default::default();
     let mut str = String::new();
 
-    for t in translations {
+    for t in resolve_conditionals(translations) {
         let next_word;
         let mut next_state: State = Default::default();
 
@@ -52,15 +154,12 @@ pub(super) fn parse_translation(translations: Vec<Text>, space_after: bool) -> S
                 let raw_stroke = stroke.to_raw();
                 // glue it if it is a number stroke
                 if NUMBER_TRANSLATION_REGEX.is_match(&raw_stroke) {
-                    // remove the hyphen
-                    next_word = raw_stroke.replace("-", "");
                     next_state.prev_is_glued = true;
                     if state.prev_is_glued {
                         state.suppress_space = true;
                     }
-                } else {
-                    next_word = raw_stroke;
                 }
+                next_word = unknown_stroke_text(raw_stroke);
             }
             Text::Attached {
                 text,
@@ -111,10 +210,10 @@ pub(super) fn parse_translation(translations: Vec<Text>, space_after: bool) -> S
                             if index < str.len() {
                                 let new_word = apply_orthography(&str[index..], &text);
                                 // replace that word with the new (orthography'ed) one
-                                str = str[..index].to_string() + &new_word;
+                                str = str[..index].to_string() + &mode.apply_attached(&new_word);
                             } else {
                                 // there was no last word, directly add the text
-                                str = str + &text;
+                                str = str + &mode.apply_attached(&text);
                             }
                             state = next_state;
                             continue;
@@ -141,17 +240,23 @@ pub(super) fn parse_translation(translations: Vec<Text>, space_after: bool) -> S
                         // reset formatting state
                         state = Default::default();
                     }
+                    StateAction::Mode(action) => {
+                        mode.apply_action(action);
+                    }
                 }
                 continue;
             }
             Text::TextAction(action) => {
-                str = perform_text_action(&str, action);
+                str = perform_text_action(&str, action, number_separators);
                 continue;
             }
+            Text::Conditional { .. } => {
+                unreachable!("conditional text should have been resolved already");
+            }
         }
 
         if !state.suppress_space {
-            str.push(SPACE);
+            str.push_str(&mode.separator());
         }
 
         let mut word = next_word;
@@ -165,6 +270,13 @@ pub(super) fn parse_translation(translations: Vec<Text>, space_after: bool) -> S
                 word.to_lowercase()
             };
         }
+        // modes take precedence over the formatting of a single word
+        if state.suppress_space {
+            // attached to the previous word, so it is not a new word for the mode
+            word = mode.apply_attached(&word);
+        } else {
+            word = mode.apply_word(&word);
+        }
         str.push_str(&word);
 
         state = next_state;
@@ -179,13 +291,65 @@ pub(super) fn parse_translation(translations: Vec<Text>, space_after: bool) -> S
             }
         }
         if !state.suppress_space {
-            str.push(SPACE);
+            str.push_str(&mode.separator());
         }
     }
 
     str
 }
 
+/// The text that an unknown stroke is printed as
+fn unknown_stroke_text(raw_stroke: String) -> String {
+    if NUMBER_TRANSLATION_REGEX.is_match(&raw_stroke) {
+        // remove the hyphen from number strokes
+        raw_stroke.replace("-", "")
+    } else {
+        raw_stroke
+    }
+}
+
+/// Replaces conditional texts with literals depending on the text of the word after them.
+///
+/// If there is no word after a conditional (yet), the text for not matching is used. Text and
+/// state actions are skipped when looking for the next word.
+fn resolve_conditionals(translations: Vec<Text>) -> Vec<Text> {
+    let mut resolved = Vec::with_capacity(translations.len());
+    // text of the word following the current one
+    let mut next_word: Option<String> = None;
+
+    // go backwards so that the next word is always known
+    for t in translations.into_iter().rev() {
+        let word = match t {
+            Text::Lit(ref text) | Text::Glued(ref text) | Text::Attached { ref text, .. } => {
+                Some(text.clone())
+            }
+            Text::UnknownStroke(ref stroke) => Some(unknown_stroke_text(stroke.clone().to_raw())),
+            Text::Conditional {
+                ref regex,
+                ref if_match,
+                ref if_not,
+            } => {
+                let is_match = next_word.as_ref().is_some_and(|word| regex.is_match(word));
+                let text = if is_match { if_match } else { if_not };
+                resolved.push(Text::Lit(text.clone()));
+                next_word = Some(text.clone()).filter(|t| !t.is_empty()).or(next_word);
+                continue;
+            }
+            Text::StateAction(_) | Text::TextAction(_) => None,
+        };
+        if let Some(word) = word {
+            let word = word.trim_start().to_string();
+            if !word.is_empty() {
+                next_word = Some(word);
+            }
+        }
+        resolved.push(t);
+    }
+
+    resolved.reverse();
+    resolved
+}
+
 /// Forces the first letter of a string to be uppercase
 fn word_change_first_letter(text: String) -> String {
     let mut chars = text.chars();
@@ -195,6 +359,15 @@ fn word_change_first_letter(text: String) -> String {
     }
 }
 
+/// Forces the first letter of a string to be lowercase
+fn word_lower_first_letter(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        None => String::new(),
+        Some(c) => c.to_lowercase().collect::<String>() + chars.as_str(),
+    }
+}
+
 /// Find the index in the text after the last space
 /// This index is 0 if there is no whitespace, and text.len() if the last char is a whitespace
 fn find_last_word_space(text: &str) -> usize {
@@ -212,6 +385,90 @@ fn find_last_word_space(text: &str) -> usize {
 // This is used for deciding what is a word when capitalizing the previous word
 const WORD_CHARS: [char; 2] = ['-', '_'];
 
+fn is_word_char(c: char) -> bool {
+    char::is_alphanumeric(c) || WORD_CHARS.contains(&c)
+}
+
+/// Find the byte range that covers the last `n` words, ignoring any non word characters after the
+/// last word. If there are less than `n` words, the range covers all of them
+fn find_last_words(text: &str, n: usize) -> Option<(usize, usize)> {
+    let mut start = None;
+    let mut end = None;
+    let mut words = 0;
+    let mut in_word = false;
+    for (i, c) in text.char_indices().rev() {
+        if is_word_char(c) {
+            if end.is_none() {
+                end = Some(i + c.len_utf8());
+            }
+            start = Some(i);
+            in_word = true;
+        } else if in_word {
+            in_word = false;
+            words += 1;
+            if words == n {
+                break;
+            }
+        }
+    }
+
+    Some((start?, end?))
+}
+
+/// Split text into runs of word characters and runs of everything else. Each run is marked with
+/// whether or not it is a word
+fn split_words(text: &str) -> Vec<(bool, &str)> {
+    let mut runs = vec![];
+    let mut start = 0;
+    let mut prev_is_word = None;
+    for (i, c) in text.char_indices() {
+        let is_word = is_word_char(c);
+        if let Some(prev) = prev_is_word {
+            if prev != is_word {
+                runs.push((prev, &text[start..i]));
+                start = i;
+            }
+        }
+        prev_is_word = Some(is_word);
+    }
+    if let Some(prev) = prev_is_word {
+        runs.push((prev, &text[start..]));
+    }
+    runs
+}
+
+/// Join the words in text, replacing whitespace between them with the separator. The first word
+/// is passed to `first` and every following word is passed to `rest`
+fn join_words(
+    text: &str,
+    separator: &str,
+    first: fn(&str) -> String,
+    rest: fn(&str) -> String,
+) -> String {
+    let mut result = String::new();
+    let mut is_first = true;
+    for (is_word, run) in split_words(text) {
+        if is_word {
+            result.push_str(&if is_first { first(run) } else { rest(run) });
+            is_first = false;
+        } else if run.chars().all(char::is_whitespace) {
+            result.push_str(separator);
+        } else {
+            result.push_str(run);
+        }
+    }
+    result
+}
+
+/// Replace the last `n` words using the transformation, leaving the rest of the text untouched
+fn transform_last_words(text: &str, n: usize, transform: impl Fn(&str) -> String) -> String {
+    if let Some((start, end)) = find_last_words(text, n) {
+        text[..start].to_string() + &transform(&text[start..end]) + &text[end..]
+    } else {
+        text.to_string()
+    }
+}
+
 /// Find the index of the last word by looking for a non alphanumeric or non word character
 fn find_last_word(text: &str) -> usize {
     // find the last non-alphanumeric (nor hyphen) character
@@ -227,7 +484,11 @@ fn find_last_word(text: &str) -> usize {
     }
 }
 
-fn perform_text_action(text: &str, action: TextAction) -> String {
+fn perform_text_action(
+    text: &str,
+    action: TextAction,
+    number_separators: &NumberSeparators,
+) -> String {
     match action {
         TextAction::SuppressSpacePrev => {
             let mut new_str = text.to_string();
@@ -255,17 +516,78 @@ fn perform_text_action(text: &str, action: TextAction) -> String {
             };
             text[..index].to_string() + &changed_case
         }
+        TextAction::CapitalizePrevWords(n) => transform_last_words(text, n, |words| {
+            split_words(words)
+                .into_iter()
+                .map(|(is_word, run)| {
+                    if is_word {
+                        capitalize_str(run)
+                    } else {
+                        run.to_string()
+                    }
+                })
+                .collect()
+        }),
+        TextAction::SameCasePrevWords(b, n) => transform_last_words(text, n, |words| {
+            if b {
+                words.to_uppercase()
+            } else {
+                words.to_lowercase()
+            }
+        }),
+        TextAction::CamelPrevWords(n) => transform_last_words(text, n, |words| {
+            join_words(words, "", word_lower_first_letter, capitalize_str)
+        }),
+        TextAction::SnakePrevWords(n) => transform_last_words(text, n, |words| {
+            join_words(words, "_", str::to_string, str::to_string)
+        }),
+        TextAction::QuotePrevWords(n) => {
+            transform_last_words(text, n, |words| format!("\"{}\"", words))
+        }
+        TextAction::CurrencyPrev(template) => {
+            transform_last_number(text, number_separators, |int, fraction| {
+                number::format_currency(int, fraction, &template, number_separators)
+            })
+        }
+        TextAction::FormatNumberPrev(style) => {
+            transform_last_number(text, number_separators, |int, fraction| {
+                number::format_number(int, fraction, &style, number_separators)
+            })
+        }
+        TextAction::WordsToNumberPrev => match number::find_last_number_words(text) {
+            Some((start, number)) => text[..start].to_string() + &number.to_string(),
+            None => text.to_string(),
+        },
+    }
+}
+
+/// Replace the number at the end of the text using the transformation. The text is unchanged if
+/// there is no number or it could not be transformed
+fn transform_last_number(
+    text: &str,
+    number_separators: &NumberSeparators,
+    transform: impl Fn(&str, Option<&str>) -> Option<String>,
+) -> String {
+    if let Some((start, int, fraction)) = number::find_last_number(text, number_separators) {
+        if let Some(formatted) = transform(int, fraction) {
+            return text[..start].to_string() + &formatted;
+        }
     }
+    text.to_string()
+}
+
+fn capitalize_str(text: &str) -> String {
+    word_change_first_letter(text.to_string())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{StateAction, TextAction};
+    use crate::{ConditionRegex, ModeAction, StateAction, TextAction};
     use plojo_core::Stroke;
 
     fn translation_diff_space_after(t: Vec<Text>) -> String {
-        parse_translation(t, false)
+        parse_translation(t, &mut Default::default(), false, &Default::default())
     }
 
     #[test]
@@ -446,40 +768,200 @@ mod tests {
     #[test]
     fn test_perform_text_action() {
         assert_eq!(
-            perform_text_action("foo bar", TextAction::SuppressSpacePrev),
+            perform_text_action(
+                "foo bar",
+                TextAction::SuppressSpacePrev,
+                &Default::default()
+            ),
             "foobar"
         );
         assert_eq!(
-            perform_text_action(" hello", TextAction::CapitalizePrev),
+            perform_text_action(" hello", TextAction::CapitalizePrev, &Default::default()),
             " Hello"
         );
         assert_eq!(
-            perform_text_action(" there are many words", TextAction::CapitalizePrev),
+            perform_text_action(
+                " there are many words",
+                TextAction::CapitalizePrev,
+                &Default::default()
+            ),
             " there are many Words"
         );
         assert_eq!(
-            perform_text_action(" no previous word ", TextAction::CapitalizePrev),
+            perform_text_action(
+                " no previous word ",
+                TextAction::CapitalizePrev,
+                &Default::default()
+            ),
             " no previous word "
         );
         assert_eq!(
-            perform_text_action(" ∅∅byteboundary", TextAction::CapitalizePrev),
+            perform_text_action(
+                " ∅∅byteboundary",
+                TextAction::CapitalizePrev,
+                &Default::default()
+            ),
             " ∅∅Byteboundary"
         );
         assert_eq!(
             // This weird character becomes 2 S's when capitalized
-            perform_text_action(" ßweird_char", TextAction::CapitalizePrev),
+            perform_text_action(
+                " ßweird_char",
+                TextAction::CapitalizePrev,
+                &Default::default()
+            ),
             " SSweird_char"
         );
         assert_eq!(
-            perform_text_action(" (symbol", TextAction::CapitalizePrev),
+            perform_text_action(" (symbol", TextAction::CapitalizePrev, &Default::default()),
             " (Symbol"
         );
         assert_eq!(
-            perform_text_action(" !symbol-hyphen", TextAction::CapitalizePrev),
+            perform_text_action(
+                " !symbol-hyphen",
+                TextAction::CapitalizePrev,
+                &Default::default()
+            ),
             " !Symbol-hyphen"
         );
     }
 
+    #[test]
+    fn test_find_last_words() {
+        assert_eq!(find_last_words(" hello big world", 2), Some((7, 16)));
+        assert_eq!(find_last_words(" hello big world", 5), Some((1, 16)));
+        assert_eq!(find_last_words(" said, 'hello.", 2), Some((1, 13)));
+        assert_eq!(find_last_words(" ∅∅byte boundary", 1), Some((12, 20)));
+        assert_eq!(find_last_words(" ... ", 1), None);
+        assert_eq!(find_last_words("", 3), None);
+    }
+
+    #[test]
+    fn test_perform_text_action_words() {
+        assert_eq!(
+            perform_text_action(
+                " the lord of the rings",
+                TextAction::CapitalizePrevWords(3),
+                &Default::default()
+            ),
+            " the lord Of The Rings"
+        );
+        assert_eq!(
+            perform_text_action(
+                " said, 'hello there.",
+                TextAction::CapitalizePrevWords(3),
+                &Default::default()
+            ),
+            " Said, 'Hello There."
+        );
+        assert_eq!(
+            perform_text_action(
+                " talk to nasa",
+                TextAction::SameCasePrevWords(true, 2),
+                &Default::default()
+            ),
+            " talk TO NASA"
+        );
+        assert_eq!(
+            perform_text_action(
+                " LOUD NOISES",
+                TextAction::SameCasePrevWords(false, 5),
+                &Default::default()
+            ),
+            " loud noises"
+        );
+        assert_eq!(
+            perform_text_action(
+                " let Number of items",
+                TextAction::CamelPrevWords(3),
+                &Default::default()
+            ),
+            " let numberOfItems"
+        );
+        assert_eq!(
+            perform_text_action(
+                " x-ray machine, nice",
+                TextAction::CamelPrevWords(3),
+                &Default::default()
+            ),
+            " x-rayMachine, Nice"
+        );
+        assert_eq!(
+            perform_text_action(
+                " let number of items;",
+                TextAction::SnakePrevWords(3),
+                &Default::default()
+            ),
+            " let number_of_items;"
+        );
+        assert_eq!(
+            perform_text_action(
+                " the word hello.",
+                TextAction::QuotePrevWords(1),
+                &Default::default()
+            ),
+            " the word \"hello\"."
+        );
+        assert_eq!(
+            perform_text_action(" hi", TextAction::QuotePrevWords(3), &Default::default()),
+            " \"hi\""
+        );
+        assert_eq!(
+            perform_text_action(" ", TextAction::QuotePrevWords(1), &Default::default()),
+            " "
+        );
+    }
+
+    fn a_or_an() -> Text {
+        Text::Conditional {
+            regex: ConditionRegex::new("(?i)[aeiou]").unwrap(),
+            if_match: "an".to_string(),
+            if_not: "a".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_conditionals() {
+        assert_eq!(
+            resolve_conditionals(vec![
+                a_or_an(),
+                Text::StateAction(StateAction::ForceCapitalize),
+                Text::Lit("apple".to_string()),
+                a_or_an(),
+                Text::Lit("pear".to_string()),
+                a_or_an(),
+                Text::UnknownStroke(Stroke::new("1-8")),
+                a_or_an(),
+            ]),
+            vec![
+                Text::Lit("an".to_string()),
+                Text::StateAction(StateAction::ForceCapitalize),
+                Text::Lit("apple".to_string()),
+                Text::Lit("a".to_string()),
+                Text::Lit("pear".to_string()),
+                Text::Lit("a".to_string()),
+                Text::UnknownStroke(Stroke::new("1-8")),
+                // nothing after it yet
+                Text::Lit("a".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_conditional() {
+        let translated = translation_diff_space_after(vec![
+            Text::StateAction(StateAction::ForceCapitalize),
+            a_or_an(),
+            Text::Lit("Orange".to_string()),
+            a_or_an(),
+            a_or_an(),
+            Text::Lit("egg".to_string()),
+        ]);
+
+        // the conditional before the other conditional sees its output as the next word
+        assert_eq!(translated, " An Orange an an egg");
+    }
+
     #[test]
     fn test_carry_capitalization() {
         let translated = translation_diff_space_after(vec![
@@ -516,7 +998,9 @@ mod tests {
                     carry_capitalization: false,
                 },
             ],
+            &mut Default::default(),
             true,
+            &Default::default(),
         );
 
         assert_eq!(translated, "helloA ");
@@ -535,7 +1019,9 @@ mod tests {
                     carry_capitalization: false,
                 },
             ],
+            &mut Default::default(),
             true,
+            &Default::default(),
         );
 
         assert_eq!(translated, "hello world ");
@@ -549,7 +1035,9 @@ mod tests {
                 Text::Glued("b".to_string()),
                 Text::Glued("c".to_string()),
             ],
+            &mut Default::default(),
             true,
+            &Default::default(),
         );
 
         assert_eq!(translated, "abc ");
@@ -557,7 +1045,8 @@ mod tests {
 
     #[test]
     fn test_space_after_empty() {
-        let translated = parse_translation(vec![], true);
+        let translated =
+            parse_translation(vec![], &mut Default::default(), true, &Default::default());
 
         assert_eq!(translated, "");
     }
@@ -580,7 +1069,9 @@ mod tests {
                     carry_capitalization: false,
                 },
             ],
+            &mut Default::default(),
             false,
+            &Default::default(),
         );
 
         assert_eq!(translated, " ©modeled");
@@ -611,9 +1102,90 @@ mod tests {
                 Text::TextAction(TextAction::CapitalizePrev),
                 Text::TextAction(TextAction::SameCasePrev(false)),
             ],
+            &mut Default::default(),
             false,
+            &Default::default(),
         );
 
         assert_eq!(translated, " HELLO (nasa HI all_caps");
     }
+
+    #[test]
+    fn test_mode_case() {
+        let translated = translation_diff_space_after(vec![
+            Text::Lit("hello".to_string()),
+            Text::StateAction(StateAction::Mode(ModeAction::Caps)),
+            Text::Lit("big".to_string()),
+            Text::Attached {
+                text: "er".to_string(),
+                joined_next: false,
+                joined_prev: AttachedType::ApplyOrthography,
+                carry_capitalization: false,
+            },
+            Text::Lit("world".to_string()),
+            Text::StateAction(StateAction::Mode(ModeAction::Title)),
+            Text::Lit("the new york".to_string()),
+            Text::StateAction(StateAction::Mode(ModeAction::ResetCase)),
+            Text::Lit("bye".to_string()),
+        ]);
+
+        assert_eq!(translated, " hello BIGGER WORLD The New York bye");
+    }
+
+    #[test]
+    fn test_mode_space() {
+        let translated = translation_diff_space_after(vec![
+            Text::Lit("call".to_string()),
+            Text::StateAction(StateAction::Mode(ModeAction::Snake)),
+            Text::Lit("my".to_string()),
+            Text::Lit("long".to_string()),
+            Text::Lit("variable".to_string()),
+            Text::Attached {
+                text: "s".to_string(),
+                joined_next: false,
+                joined_prev: AttachedType::ApplyOrthography,
+                carry_capitalization: false,
+            },
+            Text::StateAction(StateAction::Mode(ModeAction::SetSpace("-".to_string()))),
+            Text::Lit("kebab case".to_string()),
+            Text::StateAction(StateAction::Mode(ModeAction::Reset)),
+            Text::Lit("done".to_string()),
+        ]);
+
+        assert_eq!(translated, " call my_long_variables kebab-case done");
+    }
+
+    #[test]
+    fn test_mode_camel() {
+        let translated = translation_diff_space_after(vec![
+            Text::Lit("let".to_string()),
+            Text::StateAction(StateAction::Mode(ModeAction::Camel)),
+            Text::StateAction(StateAction::ForceCapitalize),
+            Text::Lit("Some".to_string()),
+            Text::Lit("long name".to_string()),
+            Text::Glued("x".to_string()),
+            Text::Glued("y".to_string()),
+            // clearing the formatting state does not clear the mode
+            Text::StateAction(StateAction::Clear),
+            Text::Lit("end".to_string()),
+        ]);
+
+        assert_eq!(translated, " let someLongNameXyEnd");
+    }
+
+    #[test]
+    fn test_mode_space_after() {
+        let translated = parse_translation(
+            vec![
+                Text::StateAction(StateAction::Mode(ModeAction::Snake)),
+                Text::Lit("hello".to_string()),
+                Text::Lit("world".to_string()),
+            ],
+            &mut Default::default(),
+            true,
+            &Default::default(),
+        );
+
+        assert_eq!(translated, "hello_world_");
+    }
 }
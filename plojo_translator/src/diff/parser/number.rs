@@ -0,0 +1,453 @@
+//! Formats numbers that have already been written (for the retroactive number actions)
+use crate::{NumberSeparators, NumberStyle};
+
+/// Find the last number in the text, which can have a fractional part. Returns the byte index of
+/// where the number starts along with its integer and fractional digits.
+///
+/// The number must be at the very end of the text (it must be the text that was just written)
+pub(super) fn find_last_number<'a>(
+    text: &'a str,
+    separators: &NumberSeparators,
+) -> Option<(usize, &'a str, Option<&'a str>)> {
+    let digits_start = |s: &str| {
+        s.rfind(|c: char| !c.is_ascii_digit())
+            // all digits are 1 byte long
+            .map_or(0, |i| i + s[i..].chars().next().map_or(1, char::len_utf8))
+    };
+
+    let start = digits_start(text);
+    if start == text.len() {
+        return None;
+    }
+    let last_digits = &text[start..];
+
+    // check if the digits are the fraction of a decimal number
+    let decimal = &separators.decimal;
+    if !decimal.is_empty() && text[..start].ends_with(decimal.as_str()) {
+        let before_decimal = &text[..start - decimal.len()];
+        let int_start = digits_start(before_decimal);
+        if int_start < before_decimal.len() {
+            return Some((int_start, &before_decimal[int_start..], Some(last_digits)));
+        }
+    }
+
+    Some((start, last_digits, None))
+}
+
+/// Add the thousands separator between every group of 3 digits
+fn group_digits(digits: &str, thousands: &str) -> String {
+    // remove leading zeros, but keep at least one digit
+    let digits = digits.trim_start_matches('0');
+    let digits = if digits.is_empty() { "0" } else { digits };
+
+    let mut result = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            result.push_str(thousands);
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// Format the number as currency with 2 decimal places, replacing the first `c` in the template
+/// with the number
+pub(super) fn format_currency(
+    int: &str,
+    fraction: Option<&str>,
+    template: &str,
+    separators: &NumberSeparators,
+) -> Option<String> {
+    let mut dollars: u128 = int.parse().ok()?;
+    // round the fraction to 2 digits
+    let fraction = fraction.unwrap_or("");
+    let mut cents: u128 = format!("{:0<2}", &fraction[..fraction.len().min(2)])
+        .parse()
+        .ok()?;
+    if matches!(fraction.chars().nth(2), Some('5'..='9')) {
+        cents += 1;
+        if cents == 100 {
+            cents = 0;
+            dollars += 1;
+        }
+    }
+
+    let number = format!(
+        "{}{}{:02}",
+        group_digits(&dollars.to_string(), &separators.thousands),
+        separators.decimal,
+        cents
+    );
+    Some(template.replacen('c', &number, 1))
+}
+
+// suffixes for the short number style, from largest to smallest
+const SHORT_SUFFIXES: [(u32, &str); 4] = [(12, "T"), (9, "B"), (6, "M"), (3, "K")];
+
+/// Format the number in one of the number styles
+pub(super) fn format_number(
+    int: &str,
+    fraction: Option<&str>,
+    style: &NumberStyle,
+    separators: &NumberSeparators,
+) -> Option<String> {
+    let with_fraction = |int: String, fraction: Option<&str>| match fraction {
+        Some(f) if !f.is_empty() => int + &separators.decimal + f,
+        _ => int,
+    };
+
+    match style {
+        NumberStyle::Grouped => Some(with_fraction(
+            group_digits(int, &separators.thousands),
+            fraction,
+        )),
+        NumberStyle::Short => {
+            let int = int.trim_start_matches('0');
+            for (i, (exponent, suffix)) in SHORT_SUFFIXES.iter().enumerate() {
+                let exponent = *exponent as usize;
+                if int.len() > exponent {
+                    // shift the decimal point and keep at most 2 decimal places
+                    let (whole, rest) = int.split_at(int.len() - exponent);
+                    let hundredths: u128 = rest[..2].parse().ok()?;
+                    let mut whole: u128 = whole.parse().ok()?;
+                    let mut hundredths = hundredths + u128::from(rest.as_bytes()[2] >= b'5');
+                    if hundredths == 100 {
+                        hundredths = 0;
+                        whole += 1;
+                    }
+                    // rounding up can reach the next suffix (999.999K is 1M)
+                    if whole == 1000 && i > 0 {
+                        return Some("1".to_string() + SHORT_SUFFIXES[i - 1].1);
+                    }
+                    let decimals = format!("{:02}", hundredths);
+                    let decimals = decimals.trim_end_matches('0');
+                    return Some(with_fraction(whole.to_string(), Some(decimals)) + suffix);
+                }
+            }
+            // too small to be shortened
+            None
+        }
+        NumberStyle::Words => number_to_words(int, fraction),
+    }
+}
+
+const ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+const TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+// each scale is 1000 times larger than the previous one
+const SCALES: [&str; 7] = [
+    "",
+    "thousand",
+    "million",
+    "billion",
+    "trillion",
+    "quadrillion",
+    "quintillion",
+];
+
+/// Write out a number below 1000 in words
+fn words_below_thousand(n: u64) -> String {
+    let mut words = vec![];
+    if n >= 100 {
+        words.push(format!("{} hundred", ONES[(n / 100) as usize]));
+    }
+    let rest = (n % 100) as usize;
+    if rest >= 20 {
+        match rest % 10 {
+            0 => words.push(TENS[rest / 10].to_string()),
+            ones => words.push(format!("{}-{}", TENS[rest / 10], ONES[ones])),
+        }
+    } else if rest > 0 {
+        words.push(ONES[rest].to_string());
+    }
+    words.join(" ")
+}
+
+/// Write out the number in English words. The fraction is read out digit by digit after "point"
+fn number_to_words(int: &str, fraction: Option<&str>) -> Option<String> {
+    let mut n: u64 = int.parse().ok()?;
+    let mut groups = vec![];
+    if n == 0 {
+        groups.push(ONES[0].to_string());
+    }
+    for scale in SCALES.iter() {
+        if n == 0 {
+            break;
+        }
+        let group = n % 1000;
+        if group > 0 {
+            let words = words_below_thousand(group);
+            groups.push(if scale.is_empty() {
+                words
+            } else {
+                format!("{} {}", words, scale)
+            });
+        }
+        n /= 1000;
+    }
+    groups.reverse();
+    let mut words = groups.join(" ");
+
+    if let Some(fraction) = fraction.filter(|f| !f.is_empty()) {
+        words.push_str(" point");
+        for digit in fraction.chars() {
+            words.push(' ');
+            words.push_str(ONES[digit.to_digit(10)? as usize]);
+        }
+    }
+    Some(words)
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum NumberWord {
+    Unit(u64),
+    Teen(u64),
+    Tens(u64),
+    Hundred,
+    Scale(u64),
+    And,
+}
+
+fn number_word(word: &str) -> Option<NumberWord> {
+    let word = word.to_lowercase();
+    if word == "hundred" {
+        Some(NumberWord::Hundred)
+    } else if word == "and" {
+        Some(NumberWord::And)
+    } else if let Some(i) = ONES.iter().position(|w| *w == word) {
+        Some(if i < 10 {
+            NumberWord::Unit(i as u64)
+        } else {
+            NumberWord::Teen(i as u64)
+        })
+    } else if let Some(i) = TENS.iter().position(|w| !w.is_empty() && *w == word) {
+        Some(NumberWord::Tens(i as u64 * 10))
+    } else {
+        SCALES
+            .iter()
+            .position(|w| !w.is_empty() && *w == word)
+            .map(|i| NumberWord::Scale(1000u64.pow(i as u32)))
+    }
+}
+
+/// Get the value of a sequence of number words, if they form a valid number
+fn number_words_value(words: &[NumberWord]) -> Option<u64> {
+    use NumberWord::*;
+
+    if words == [Unit(0)] {
+        return Some(0);
+    }
+
+    let mut total: u64 = 0;
+    let mut current: u64 = 0;
+    let mut prev: Option<NumberWord> = None;
+    let mut last_scale: Option<u64> = None;
+    // whether a word can start a new part of the number (ex: after "hundred" or "thousand")
+    let after_separator = |prev: Option<NumberWord>| {
+        matches!(prev, None | Some(Hundred) | Some(Scale(_)) | Some(And))
+    };
+    for &word in words {
+        match word {
+            And if !matches!(prev, Some(Hundred) | Some(Scale(_))) => return None,
+            Unit(0) => return None,
+            Unit(n) if after_separator(prev) || matches!(prev, Some(Tens(_))) => current += n,
+            Teen(n) | Tens(n) if after_separator(prev) => current += n,
+            Hundred
+                if current < 100
+                    && matches!(prev, Some(Unit(_)) | Some(Teen(_)) | Some(Tens(_))) =>
+            {
+                current *= 100
+            }
+            Scale(scale)
+                if current > 0
+                    && !matches!(prev, Some(And))
+                    && last_scale.is_none_or(|last| scale < last) =>
+            {
+                total = total.checked_add(current.checked_mul(scale)?)?;
+                current = 0;
+                last_scale = Some(scale);
+            }
+            And => {}
+            _ => return None,
+        }
+        prev = Some(word);
+    }
+
+    match prev {
+        None | Some(And) => None,
+        _ => total.checked_add(current),
+    }
+}
+
+/// Find the number written in words at the end of the text (ex: "twenty-three" or "one hundred
+/// and five"). Returns the byte index of where the number starts along with its value
+pub(super) fn find_last_number_words(text: &str) -> Option<(usize, u64)> {
+    // the words at the end of the text that could be part of a number, along with where they start
+    let mut words: Vec<(usize, Vec<NumberWord>)> = vec![];
+    let mut end = text.len();
+    loop {
+        let start = text[..end].rfind(char::is_whitespace).map_or(0, |i| {
+            i + text[i..].chars().next().map_or(1, char::len_utf8)
+        });
+        if start == end {
+            break;
+        }
+        let parts: Option<Vec<_>> = text[start..end].split('-').map(number_word).collect();
+        match parts {
+            Some(parts) => words.push((start, parts)),
+            None => break,
+        }
+        if start == 0 {
+            break;
+        }
+        end = text[..start].trim_end().len();
+    }
+    words.reverse();
+
+    // use the longest sequence of words that makes a valid number
+    (0..words.len()).find_map(|i| {
+        let parts: Vec<_> = words[i..].iter().flat_map(|(_, p)| p.clone()).collect();
+        number_words_value(&parts).map(|value| (words[i].0, value))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_last_number() {
+        let separators = NumberSeparators::default();
+        assert_eq!(
+            find_last_number(" costs 1250", &separators),
+            Some((7, "1250", None))
+        );
+        assert_eq!(
+            find_last_number(" d12.50", &separators),
+            Some((2, "12", Some("50")))
+        );
+        assert_eq!(find_last_number(" .50", &separators), Some((2, "50", None)));
+        assert_eq!(find_last_number(" 12 dollars", &separators), None);
+        assert_eq!(find_last_number("", &separators), None);
+        assert_eq!(
+            find_last_number("3,5", &NumberSeparators::new(".", ",")),
+            Some((0, "3", Some("5")))
+        );
+    }
+
+    #[test]
+    fn test_group_digits() {
+        assert_eq!(group_digits("1250", ","), "1,250");
+        assert_eq!(group_digits("125", ","), "125");
+        assert_eq!(group_digits("1234567", "."), "1.234.567");
+        assert_eq!(group_digits("000", ","), "0");
+    }
+
+    #[test]
+    fn test_format_currency() {
+        let separators = NumberSeparators::default();
+        assert_eq!(
+            format_currency("1250", None, "$c", &separators).unwrap(),
+            "$1,250.00"
+        );
+        assert_eq!(
+            format_currency("3", Some("5"), "c USD", &separators).unwrap(),
+            "3.50 USD"
+        );
+        assert_eq!(
+            format_currency("9", Some("999"), "$c", &separators).unwrap(),
+            "$10.00"
+        );
+        assert_eq!(
+            format_currency("1250", Some("5"), "c €", &NumberSeparators::new(".", ",")).unwrap(),
+            "1.250,50 €"
+        );
+        // only the first `c` is the number
+        assert_eq!(
+            format_currency("3", None, "c cents", &separators).unwrap(),
+            "3.00 cents"
+        );
+    }
+
+    #[test]
+    fn test_format_number() {
+        let separators = NumberSeparators::default();
+        let grouped = |int, fraction| {
+            format_number(int, fraction, &NumberStyle::Grouped, &separators).unwrap()
+        };
+        let short = |int| format_number(int, None, &NumberStyle::Short, &separators);
+        assert_eq!(grouped("1250", None), "1,250");
+        assert_eq!(grouped("1250", Some("75")), "1,250.75");
+        assert_eq!(short("1250000"), Some("1.25M".to_string()));
+        assert_eq!(short("1999"), Some("2K".to_string()));
+        assert_eq!(short("1500000000"), Some("1.5B".to_string()));
+        assert_eq!(short("20000"), Some("20K".to_string()));
+        assert_eq!(short("999"), None);
+        assert_eq!(short("999999"), Some("1M".to_string()));
+        assert_eq!(short("999994999"), Some("999.99M".to_string()));
+        assert_eq!(short("999999999"), Some("1B".to_string()));
+        assert_eq!(short("999999999999999"), Some("1000T".to_string()));
+    }
+
+    #[test]
+    fn test_number_to_words() {
+        let words = |int, fraction| number_to_words(int, fraction).unwrap();
+        assert_eq!(words("0", None), "zero");
+        assert_eq!(words("23", None), "twenty-three");
+        assert_eq!(words("40", None), "forty");
+        assert_eq!(words("105", None), "one hundred five");
+        assert_eq!(words("1250", None), "one thousand two hundred fifty");
+        assert_eq!(words("2000017", None), "two million seventeen");
+        assert_eq!(words("3", Some("05")), "three point zero five");
+        assert_eq!(number_to_words("99999999999999999999", None), None);
+    }
+
+    #[test]
+    fn test_find_last_number_words() {
+        assert_eq!(find_last_number_words(" page twenty-three"), Some((6, 23)));
+        assert_eq!(find_last_number_words(" twenty three"), Some((1, 23)));
+        assert_eq!(
+            find_last_number_words("One hundred and five"),
+            Some((0, 105))
+        );
+        assert_eq!(
+            find_last_number_words(" one thousand two hundred fifty"),
+            Some((1, 1250))
+        );
+        assert_eq!(find_last_number_words(" fifteen hundred"), Some((1, 1500)));
+        assert_eq!(find_last_number_words(" zero"), Some((1, 0)));
+        // only the words that make a valid number are used
+        assert_eq!(find_last_number_words(" one two"), Some((5, 2)));
+        assert_eq!(find_last_number_words(" and five"), Some((5, 5)));
+        assert_eq!(find_last_number_words(" thousand"), None);
+        assert_eq!(
+            find_last_number_words(" one hundred twenty hundred"),
+            Some((13, 2000))
+        );
+        assert_eq!(find_last_number_words(" five hundred and"), None);
+        assert_eq!(find_last_number_words(" five apples"), None);
+        assert_eq!(find_last_number_words(""), None);
+    }
+}
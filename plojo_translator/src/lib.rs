@@ -2,8 +2,9 @@
 extern crate lazy_static;
 
 use dictionary::Dictionary;
-use diff::translation_diff;
+use diff::{mode_after, translation_diff, Mode};
 use plojo_core::{Command, Stroke, Translator};
+use regex::Regex;
 use serde::Deserialize;
 use std::{error::Error, hash::Hash};
 
@@ -42,12 +43,60 @@ enum Text {
     },
     // glued strokes only attach to other glued strokes
     Glued(String),
+    // text that depends on whether or not the next word matches a regex
+    Conditional {
+        regex: ConditionRegex,
+        if_match: String,
+        if_not: String,
+    },
     // changes the state for suppressing space, capitalizing, etc. the next word
     StateAction(StateAction),
     // text actions can only affect the text before it
     TextAction(TextAction),
 }
 
+/// A regex that is compiled when the dictionary is loaded. It only matches at the start of the
+/// next word, and is compared and hashed by its pattern
+#[derive(Debug, Clone)]
+struct ConditionRegex {
+    pattern: String,
+    regex: Regex,
+}
+
+impl ConditionRegex {
+    fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(Self {
+            pattern: pattern.to_string(),
+            regex: Regex::new(&format!("^(?:{})", pattern))?,
+        })
+    }
+
+    fn is_match(&self, word: &str) -> bool {
+        self.regex.is_match(word)
+    }
+}
+
+impl PartialEq for ConditionRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern
+    }
+}
+
+impl Eq for ConditionRegex {}
+
+impl<'de> Deserialize<'de> for ConditionRegex {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Self::new(&pattern).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Hash for ConditionRegex {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.pattern.hash(state);
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Hash, Eq, Deserialize)]
 enum AttachedType {
     ApplyOrthography,
@@ -70,6 +119,26 @@ enum StateAction {
     ForceCapitalize,
     SameCase(bool), // apply all upper (true) or lower (false) case
     Clear,
+    // changes the output mode, which persists across words until it is changed again
+    Mode(ModeAction),
+}
+
+/// Plover-style output modes (`{MODE:CAPS}`, `{MODE:SNAKE}`, etc.)
+#[derive(Debug, PartialEq, Clone, Hash, Eq, Deserialize)]
+enum ModeAction {
+    Caps,
+    Lower,
+    Title,
+    // lowercase first word, capitalize the following words and remove spaces
+    Camel,
+    // join words with underscores
+    Snake,
+    // join words with a custom separator
+    SetSpace(String),
+    ResetCase,
+    ResetSpace,
+    // reset both the case and the space
+    Reset,
 }
 
 #[allow(clippy::enum_variant_names)]
@@ -78,6 +147,55 @@ enum TextAction {
     CapitalizePrev,
     SuppressSpacePrev,
     SameCasePrev(bool), // apply all upper (true) or lower (false) case
+    // capitalize each of the previous n words
+    CapitalizePrevWords(usize),
+    // apply all upper (true) or lower (false) case to the previous n words
+    SameCasePrevWords(bool, usize),
+    // join the previous n words into camelCase
+    CamelPrevWords(usize),
+    // join the previous n words into snake_case
+    SnakePrevWords(usize),
+    // wrap the previous n words in double quotes
+    QuotePrevWords(usize),
+    // format the previous number as currency, replacing the first `c` in the template with the
+    // number
+    CurrencyPrev(String),
+    // format the previous number in a certain style
+    FormatNumberPrev(NumberStyle),
+    // convert the number written in words before this into digits
+    WordsToNumberPrev,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Deserialize)]
+enum NumberStyle {
+    // add thousands separators (ex: 1,250)
+    Grouped,
+    // shorten large numbers with a suffix (ex: 1.25M)
+    Short,
+    // write out the number in English words (ex: twenty-three)
+    Words,
+}
+
+/// Separators used when formatting numbers with the retroactive number actions
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+pub struct NumberSeparators {
+    pub thousands: String,
+    pub decimal: String,
+}
+
+impl NumberSeparators {
+    pub fn new(thousands: &str, decimal: &str) -> Self {
+        Self {
+            thousands: thousands.to_string(),
+            decimal: decimal.to_string(),
+        }
+    }
+}
+
+impl Default for NumberSeparators {
+    fn default() -> Self {
+        Self::new(",", ".")
+    }
 }
 
 /// The standard translator is very similar in feature to Plover and other CAT software.
@@ -93,6 +211,15 @@ pub struct StandardTranslator {
     retrospective_add_space: Vec<Stroke>,
     add_space_insert: Option<Stroke>,
     space_after: bool,
+    number_separators: NumberSeparators,
+    // formatting from the text before the cursor, which applies to the first stroke
+    context: Vec<Text>,
+    // the output mode from strokes that are no longer in the stroke buffer
+    mode: Mode,
+    // the output mode after each stroke in the stroke buffer
+    stroke_modes: Vec<Mode>,
+    // whether to output how much of the text could still change with the next stroke
+    pending_output: bool,
 }
 
 // most number of strokes to stroke in prev_strokes; limits undo to this many strokes
@@ -115,7 +242,7 @@ fn is_text(translation: Translation) -> bool {
             // check if at least one is non empty text
             for text in texts {
                 match text {
-                    Text::UnknownStroke(_) => return true,
+                    Text::UnknownStroke(_) | Text::Conditional { .. } => return true,
                     Text::Attached { text, .. } | Text::Glued(text) | Text::Lit(text) => {
                         if !text.is_empty() {
                             return true;
@@ -129,6 +256,29 @@ fn is_text(translation: Translation) -> bool {
     }
 }
 
+/// Formatting that the text before the cursor implies for the next word. At the start of a
+/// document or sentence, the word is capitalized, and no space is added after whitespace
+fn context_texts(text_before_cursor: &str) -> Vec<Text> {
+    let mut texts = vec![];
+    if text_before_cursor.is_empty() || text_before_cursor.ends_with(char::is_whitespace) {
+        texts.push(Text::Attached {
+            text: "".to_string(),
+            joined_next: true,
+            joined_prev: AttachedType::DoNotAttach,
+            carry_capitalization: false,
+        });
+    }
+
+    // closing quotes and brackets can come after the end of a sentence
+    let sentence = text_before_cursor
+        .trim_end()
+        .trim_end_matches(&['"', '\'', ')', ']', '”', '’'][..]);
+    if sentence.is_empty() || sentence.ends_with(&['.', '!', '?'][..]) {
+        texts.push(Text::StateAction(StateAction::ForceCapitalize));
+    }
+    texts
+}
+
 impl StandardTranslator {
     /// Creates a translator that takes the raw dictionary string from one or more dictionaries. The
     /// dictionaries further down in the list can override the earlier dictionaries.
@@ -152,20 +302,125 @@ impl StandardTranslator {
             assert!(add_space_insert.is_some());
         }
 
-        Ok(Self {
+        let mut translator = Self {
             prev_strokes: starting_strokes,
             dict,
             retrospective_add_space,
             add_space_insert,
             space_after,
-        })
+            number_separators: Default::default(),
+            context: vec![],
+            mode: Default::default(),
+            stroke_modes: vec![],
+            pending_output: false,
+        };
+        translator.update_modes_from(0);
+        Ok(translator)
+    }
+
+    /// Use different separators for formatting numbers (the default is `1,250.00`)
+    pub fn with_number_separators(mut self, number_separators: NumberSeparators) -> Self {
+        self.number_separators = number_separators;
+        self.update_modes_from(0);
+        self
+    }
+
+    /// After every stroke, output a `Command::Pending` with how many characters at the end of the
+    /// text could still change because the last strokes are the start of a longer outline
+    pub fn with_pending_output(mut self, pending_output: bool) -> Self {
+        self.pending_output = pending_output;
+        self
+    }
+
+    /// Add the pending command to the commands if it is enabled
+    fn add_pending(&self, mut commands: Vec<Command>) -> Vec<Command> {
+        if self.pending_output {
+            commands.push(Command::Pending(self.pending_len()));
+        }
+        commands
+    }
+
+    /// Find how many characters at the end of the text come from strokes that could be the start
+    /// of a longer outline
+    fn pending_len(&self) -> usize {
+        let len = self.prev_strokes.len();
+        let start = len.saturating_sub(MAX_TRANSLATION_STROKE_LEN);
+        // use the most strokes that could be the start of a longer outline
+        for tail in (1..=len - start).rev() {
+            if self.dict.is_prefix(&self.prev_strokes[len - tail..]) {
+                let without_tail = self.translate_strokes(start, len - tail);
+                let with_tail = self.translate_strokes(start, len);
+                // the text that the tail strokes changed
+                return match translation_diff(
+                    &without_tail,
+                    &with_tail,
+                    &self.mode_at(start),
+                    self.space_after,
+                    &self.number_separators,
+                )
+                .as_slice()
+                {
+                    [Command::Replace(_, text)] => text.chars().count(),
+                    _ => 0,
+                };
+            }
+        }
+        0
+    }
+
+    /// Translate the strokes, starting with the context if the strokes are at the start of the
+    /// stroke buffer
+    fn translate_strokes(&self, start: usize, end: usize) -> Vec<Translation> {
+        let mut translations = self.dict.translate(&self.prev_strokes[start..end]);
+        if start == 0 && !self.context.is_empty() {
+            translations.insert(0, Translation::Text(self.context.clone()));
+        }
+        translations
+    }
+
+    /// The output mode before the stroke at the index in the stroke buffer. Modes are kept for
+    /// the strokes before the translated strokes so that they are not lost when the mode stroke
+    /// is no longer translated
+    fn mode_at(&self, index: usize) -> Mode {
+        match index {
+            0 => self.mode.clone(),
+            index => self.stroke_modes[index - 1].clone(),
+        }
+    }
+
+    /// Find the output mode after each stroke from the index on, after the strokes changed. Like
+    /// translating, only the last strokes before each one are translated, starting from the mode
+    /// before them
+    fn update_modes_from(&mut self, index: usize) {
+        self.stroke_modes.truncate(index);
+        for end in index + 1..=self.prev_strokes.len() {
+            let start = end.saturating_sub(MAX_TRANSLATION_STROKE_LEN);
+            let mode = mode_after(
+                &self.translate_strokes(start, end),
+                &self.mode_at(start),
+                self.space_after,
+                &self.number_separators,
+            );
+            self.stroke_modes.push(mode);
+        }
+    }
+
+    /// Remove the strokes before the index from the stroke buffer, keeping the mode they set
+    fn remove_strokes_before(&mut self, index: usize) {
+        self.mode = self.mode_at(index);
+        self.prev_strokes.drain(..index);
+        self.stroke_modes.drain(..index);
+        if index > 0 {
+            // the context was for the first stroke, which is gone
+            self.context.clear();
+        }
     }
 }
 
 impl Translator for StandardTranslator {
     fn translate(&mut self, stroke: Stroke) -> Vec<Command> {
         if self.prev_strokes.len() > MAX_STROKE_BUFFER {
-            self.prev_strokes.remove(0);
+            self.remove_strokes_before(1);
         }
 
         // translate only latest strokes
@@ -175,7 +430,8 @@ impl Translator for StandardTranslator {
             0
         };
 
-        let old_translations = self.dict.translate(&self.prev_strokes[start..]);
+        let old_translations = self.translate_strokes(start, self.prev_strokes.len());
+        let start_mode = self.mode_at(start);
 
         // add a space if necessary
         if self.retrospective_add_space.contains(&stroke) {
@@ -192,30 +448,46 @@ impl Translator for StandardTranslator {
             // add a space
             if let Some(space) = self.add_space_insert.clone() {
                 self.prev_strokes.insert(index, space);
+                self.update_modes_from(index);
             }
         } else {
             self.prev_strokes.push(stroke);
+            self.update_modes_from(self.prev_strokes.len() - 1);
         }
 
-        let new_translations = self.dict.translate(&self.prev_strokes[start..]);
+        let new_translations = self.translate_strokes(start, self.prev_strokes.len());
 
-        translation_diff(&old_translations, &new_translations, self.space_after)
+        let commands = translation_diff(
+            &old_translations,
+            &new_translations,
+            &start_mode,
+            self.space_after,
+            &self.number_separators,
+        );
+        self.add_pending(commands)
     }
 
     fn undo(&mut self) -> Vec<Command> {
-        let old_translations = self.dict.translate(&self.prev_strokes);
+        let old_translations = self.translate_strokes(0, self.prev_strokes.len());
 
-        // keep on removing strokes as long as they are the same (when diffed)
-        while !self.prev_strokes.is_empty() {
+        // keep on removing strokes as long as they are the same (when diffed) and do not change
+        // the output mode
+        while let Some(old_mode) = self.stroke_modes.pop() {
             self.prev_strokes.pop();
-            let new_translations = self.dict.translate(&self.prev_strokes);
-            let diff = translation_diff(&old_translations, &new_translations, self.space_after);
-            if diff != vec![Command::NoOp] {
-                return diff;
+            let new_translations = self.translate_strokes(0, self.prev_strokes.len());
+            let diff = translation_diff(
+                &old_translations,
+                &new_translations,
+                &self.mode,
+                self.space_after,
+                &self.number_separators,
+            );
+            if diff != vec![Command::NoOp] || old_mode != self.mode_at(self.prev_strokes.len()) {
+                return self.add_pending(diff);
             }
         }
 
-        return vec![Command::NoOp];
+        return self.add_pending(vec![Command::NoOp]);
     }
 
     /// Handle a command for the translator.
@@ -228,24 +500,63 @@ impl Translator for StandardTranslator {
             "clear_prev_strokes" => {
                 // remove every stroke before the last, because that stroke triggered this command
                 // and the last stroke could have text_after text that needs to be preserved
-                let mut v = Vec::with_capacity(MAX_STROKE_BUFFER);
-                if let Some(last) = self.prev_strokes.pop() {
-                    v.push(last);
-                }
-                self.prev_strokes = v;
+                self.remove_strokes_before(self.prev_strokes.len().saturating_sub(1));
+                // the text before the first stroke is no longer known
+                self.context.clear();
+                self.mode = self.mode.clone().restarted();
+                self.update_modes_from(0);
             }
             "toggle_space_after" => {
                 self.space_after = !self.space_after;
+                self.update_modes_from(0);
             }
             _c => eprintln!("[WARN]: the standard translator cannot handle {:?}", _c),
         }
     }
+
+    /// Clears the stroke buffer completely (unlike the "clear_prev_strokes" command, which keeps
+    /// the stroke that triggered it). The output mode stays on, but starts over with the next word
+    fn reset(&mut self) {
+        self.remove_strokes_before(self.prev_strokes.len());
+        self.context.clear();
+        self.mode = self.mode.clone().restarted();
+    }
+
+    /// The context is only used before anything is written, since the translator knows the text
+    /// before the cursor after that
+    fn set_context(&mut self, text_before_cursor: &str) {
+        if self.prev_strokes.is_empty() {
+            self.context = context_texts(text_before_cursor);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_context_texts() {
+        let suppress_space = Text::Attached {
+            text: "".to_string(),
+            joined_next: true,
+            joined_prev: AttachedType::DoNotAttach,
+            carry_capitalization: false,
+        };
+        let capitalize = Text::StateAction(StateAction::ForceCapitalize);
+        assert_eq!(
+            context_texts(""),
+            vec![suppress_space.clone(), capitalize.clone()]
+        );
+        assert_eq!(
+            context_texts("Done. "),
+            vec![suppress_space.clone(), capitalize.clone()]
+        );
+        assert_eq!(context_texts("He said \"stop!\""), vec![capitalize]);
+        assert_eq!(context_texts("Hello, "), vec![suppress_space]);
+        assert_eq!(context_texts("Hello"), vec![]);
+    }
+
     #[test]
     fn test_is_text() {
         assert_eq!(
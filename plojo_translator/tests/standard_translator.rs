@@ -1,5 +1,6 @@
-use plojo_core::{Command, Key, Modifier, SpecialKey, Stroke, Translator};
-use plojo_translator::StandardTranslator;
+use plojo_core::{Command, Controller, Key, Modifier, SpecialKey, Stroke, Translator};
+use plojo_output_buffer::BufferController;
+use plojo_translator::{NumberSeparators, StandardTranslator};
 
 /// Blackbox assert macro for better line number tracing
 /// Expect that pressing stroke(s) causes a certain output
@@ -126,6 +127,14 @@ impl Blackbox {
                     Command::TranslatorCommand(cmd) => {
                         self.translator.handle_command(cmd);
                     }
+                    Command::Pending(_) | Command::Wait(_) => {}
+                    Command::KeySequence(keys) => self.output_keys.extend(keys),
+                    Command::Unicode(_) | Command::MoveCursor(..) => {
+                        panic!(
+                            "Not expecting {:?} to be outputted from the blackbox",
+                            command
+                        );
+                    }
                 }
             }
         }
@@ -692,3 +701,354 @@ fn command_stroke_suffix_folding() {
     b_expect!(b, "H-L", " hello R-RS hello");
     b_expect!(b, "*/*", " hello");
 }
+
+#[test]
+fn output_modes() {
+    let mut b = Blackbox::new(
+        r#"
+            "KA*PS": "{MODE:CAPS}",
+            "KA*PL": "{MODE:CAMEL}",
+            "SNA*EUBG": "{MODE:SNAKE}",
+            "R*ES": "{MODE:RESET}",
+            "H-L": "hello",
+            "WORLD": "world",
+            "-S": "{^s}"
+        "#,
+    );
+    b_expect!(b, "H-L/KA*PS/WORLD", " hello WORLD");
+    b_expect!(b, "H-L", " hello WORLD HELLO");
+    b_expect!(
+        b,
+        "R*ES/SNA*EUBG/H-L/WORLD/-S",
+        " hello WORLD HELLO hello_worlds"
+    );
+    b_expect!(
+        b,
+        "R*ES/KA*PL/H-L/WORLD",
+        " hello WORLD HELLO hello_worlds helloWorld"
+    );
+    b_expect!(
+        b,
+        "R*ES/H-L",
+        " hello WORLD HELLO hello_worlds helloWorld hello"
+    );
+}
+
+#[test]
+fn output_modes_many_words() {
+    let mut b = Blackbox::new(
+        r#"
+            "KA*PS": "{MODE:CAPS}",
+            "KA*PL": "{MODE:CAMEL}",
+            "R*ES": "{MODE:RESET}",
+            "H-L": "hello"
+        "#,
+    );
+    // the mode stroke is no longer translated after 10 strokes, but the mode stays on
+    b_expect!(b, "KA*PS", "");
+    for _ in 0..12 {
+        b.lookup_and_dispatch("H-L");
+    }
+    assert_eq!(b.output, " HELLO".repeat(12));
+    b_expect!(b, "R*ES/H-L", " HELLO".repeat(12) + " hello");
+
+    // camel case keeps joining words, even after the stroke buffer is full
+    let mut b = Blackbox::new(
+        r#"
+            "KA*PL": "{MODE:CAMEL}",
+            "H-L": "hello"
+        "#,
+    );
+    b_expect!(b, "KA*PL", "");
+    for _ in 0..60 {
+        b.lookup_and_dispatch("H-L");
+    }
+    assert_eq!(b.output, " hello".to_string() + &"Hello".repeat(59));
+}
+
+#[test]
+fn output_modes_undo() {
+    let mut b = Blackbox::new(
+        r#"
+            "SNA*EUBG": "{MODE:SNAKE}",
+            "H-L": "hello",
+            "WORLD": "world"
+        "#,
+    );
+    b_expect!(b, "H-L/SNA*EUBG/H-L/WORLD", " hello hello_world");
+    b_expect!(b, "*", " hello hello");
+    b_expect!(b, "*", " hello");
+    b_expect!(b, "WORLD/H-L", " hello world_hello");
+    b_expect!(b, "*/*", " hello");
+    // the mode stroke has no text, but undoing it only turns off the mode
+    b_expect!(b, "*", " hello");
+    b_expect!(b, "H-L/WORLD", " hello hello world");
+    b_expect!(b, "*/*/*", "");
+}
+
+#[test]
+fn retro_word_actions() {
+    let mut b = Blackbox::new(
+        r#"
+            "T-LT": "{*-|:3}",
+            "KA*PL": "{*CAMEL:3}",
+            "KW-T": "{*QUOTE}",
+            "TP-PL": "{.}",
+            "-T": "the",
+            "HRORD": "lord",
+            "-F": "of",
+            "RORBGS": "rings"
+        "#,
+    );
+    b_expect!(b, "-T/HRORD/-F/-T/RORBGS", " the lord of the rings");
+    b_expect!(b, "T-LT", " the lord Of The Rings");
+    b_expect!(b, "TP-PL/KW-T", " the lord Of The \"Rings\".");
+    b_expect!(b, "*", " the lord Of The Rings.");
+    b_expect!(b, "*/*", " the lord of the rings");
+    b_expect!(b, "KA*PL", " the lord ofTheRings");
+    b_expect!(b, "*", " the lord of the rings");
+}
+
+#[test]
+fn conditional_next_word() {
+    let mut b = Blackbox::new(
+        r#"
+            "AEU": "{=(?i)([aeiou]|8|11|18)/an/a}",
+            "APL": "apple",
+            "PAEUR": "pear",
+            "KPA": "{-|}"
+        "#,
+    );
+    b_expect!(b, "KPA/AEU", " A");
+    // the previous word is corrected once the next word is known
+    b_expect!(b, "APL", " An apple");
+    b_expect!(b, "AEU/PAEUR", " An apple a pear");
+    b_expect!(b, "AEU/1-8", " An apple a pear an 18");
+    b_expect!(b, "*", " An apple a pear a");
+    b_expect!(b, "*", " An apple a pear");
+}
+
+#[test]
+fn retro_number_formatting() {
+    let mut b = Blackbox::new(
+        r#"
+            "TKHRA*RS": "{*($c)}",
+            "TKPW*RP": "{*NUM:GROUP}",
+            "SHO*RT": "{*NUM:SHORT}",
+            "P-P": "{^.^}",
+            "KOFT": "costs"
+        "#,
+    );
+    b_expect!(b, "KOFT/12/50", " costs 1250");
+    b_expect!(b, "TKHRA*RS", " costs $1,250.00");
+    b_expect!(b, "*", " costs 1250");
+    b_expect!(b, "TKPW*RP", " costs 1,250");
+    b_expect!(b, "*/P-P/5", " costs 1250.5");
+    b_expect!(b, "TKHRA*RS", " costs $1,250.50");
+    b_expect!(b, "KOFT/1/2-5/0/0/0/0", " costs $1,250.50 costs 1250000");
+    b_expect!(b, "SHO*RT", " costs $1,250.50 costs 1.25M");
+}
+
+#[test]
+fn retro_number_words() {
+    let mut b = Blackbox::new(
+        r#"
+            "WO*RDZ": "{*NUM:WORDS}",
+            "TKEUGS": "{*NUM:DIGITS}",
+            "PAEUPBLG": "page",
+            "TWEPB": "twenty{^-^}",
+            "THRAOE": "three",
+            "HUPBD": "hundred"
+        "#,
+    );
+    b_expect!(b, "PAEUPBLG/2/3", " page 23");
+    b_expect!(b, "WO*RDZ", " page twenty-three");
+    b_expect!(b, "*", " page 23");
+    b_expect!(
+        b,
+        "PAEUPBLG/THRAOE/HUPBD/TWEPB/THRAOE",
+        " page 23 page three hundred twenty-three"
+    );
+    b_expect!(b, "TKEUGS", " page 23 page 323");
+    b_expect!(b, "*", " page 23 page three hundred twenty-three");
+}
+
+#[test]
+fn retro_number_separators() {
+    let dict = r#"{ "TKHRA*RS": "{*(c €)}", "P-P": "{^,^}" }"#.to_string();
+    let mut translator = StandardTranslator::new(vec![dict], vec![], vec![], None, false)
+        .unwrap()
+        .with_number_separators(NumberSeparators::new(".", ","));
+    let mut output = vec![];
+    for s in &["1", "2-5", "0", "P-P", "5", "TKHRA*RS"] {
+        output.append(&mut translator.translate(Stroke::new(s)));
+    }
+    // only the text after the shared "1" is replaced
+    assert_eq!(
+        output.last(),
+        Some(&Command::Replace(5, ".250,50 €".to_string()))
+    );
+}
+
+#[test]
+fn context_from_text_before_cursor() {
+    let dict = r#"{ "H-L": "hello", "WORLD": "world" }"#.to_string();
+    let new_translator =
+        || StandardTranslator::new(vec![dict.clone()], vec![], vec![], None, false).unwrap();
+
+    // start of a sentence after a space
+    let mut translator = new_translator();
+    translator.set_context("It works. ");
+    assert_eq!(
+        translator.translate(Stroke::new("H-L")),
+        vec![Command::add_text("Hello")]
+    );
+    assert_eq!(
+        translator.translate(Stroke::new("WORLD")),
+        vec![Command::add_text(" world")]
+    );
+    assert_eq!(translator.undo(), vec![Command::replace_text(6, "")]);
+    assert_eq!(translator.undo(), vec![Command::replace_text(5, "")]);
+
+    // in the middle of a sentence, without a space
+    let mut translator = new_translator();
+    translator.set_context("It said");
+    assert_eq!(
+        translator.translate(Stroke::new("H-L")),
+        vec![Command::add_text(" hello")]
+    );
+    // the context is ignored once something has been written
+    translator.set_context("");
+    assert_eq!(
+        translator.translate(Stroke::new("WORLD")),
+        vec![Command::add_text(" world")]
+    );
+}
+
+#[test]
+fn context_after_full_stroke_buffer() {
+    let dict = r#"{ "H-L": "hello" }"#.to_string();
+    let mut translator = StandardTranslator::new(vec![dict], vec![], vec![], None, false).unwrap();
+    let mut buffer = BufferController::default();
+    translator.set_context("");
+    for _ in 0..60 {
+        for command in translator.translate(Stroke::new("H-L")) {
+            buffer.dispatch(command);
+        }
+    }
+    // the context only applied to the first stroke, which can no longer be undone
+    for _ in 0..60 {
+        for command in translator.undo() {
+            buffer.dispatch(command);
+        }
+    }
+    assert_eq!(buffer.text(), "Hello".to_string() + &" hello".repeat(8));
+}
+
+#[test]
+fn reset_on_focus_change() {
+    let dict = r#"{ "H-L": "hello", "WORLD": "world", "H-L/WORLD": "hello world!" }"#.to_string();
+    let mut translator = StandardTranslator::new(vec![dict], vec![], vec![], None, false).unwrap();
+    translator.translate(Stroke::new("H-L"));
+    translator.reset();
+    // the previous stroke is in another text field, so it cannot be corrected
+    assert_eq!(
+        translator.translate(Stroke::new("WORLD")),
+        vec![Command::add_text(" world")]
+    );
+    assert_eq!(translator.undo(), vec![Command::replace_text(6, "")]);
+    assert_eq!(translator.undo(), vec![Command::NoOp]);
+
+    // the context of the new text field is used after a reset
+    translator.reset();
+    translator.set_context("");
+    assert_eq!(
+        translator.translate(Stroke::new("H-L")),
+        vec![Command::add_text("Hello")]
+    );
+}
+
+#[test]
+fn pending_output() {
+    let dict = r#"{
+        "H-L": "hello",
+        "WORLD": "world",
+        "PWEUG": "big",
+        "PWEUG/APL": "bigamy",
+        "PWEUG/APL/OUS": "bigamous",
+        "APL": "am"
+    }"#
+    .to_string();
+    let mut translator = StandardTranslator::new(vec![dict], vec![], vec![], None, false)
+        .unwrap()
+        .with_pending_output(true);
+
+    assert_eq!(
+        translator.translate(Stroke::new("H-L")),
+        vec![Command::add_text(" hello"), Command::Pending(0)]
+    );
+    // "big" could become "bigamy"
+    assert_eq!(
+        translator.translate(Stroke::new("PWEUG")),
+        vec![Command::add_text(" big"), Command::Pending(4)]
+    );
+    // "bigamy" could become "bigamous"
+    assert_eq!(
+        translator.translate(Stroke::new("APL")),
+        vec![Command::add_text("amy"), Command::Pending(7)]
+    );
+    assert_eq!(
+        translator.translate(Stroke::new("OUS")),
+        vec![Command::replace_text(1, "ous"), Command::Pending(0)]
+    );
+    assert_eq!(
+        translator.undo(),
+        vec![Command::replace_text(3, "y"), Command::Pending(7)]
+    );
+
+    // a single stroke that could be the start of a longer outline
+    translator.reset();
+    assert_eq!(
+        translator.translate(Stroke::new("PWEUG")),
+        vec![Command::add_text(" big"), Command::Pending(4)]
+    );
+    assert_eq!(
+        translator.undo(),
+        vec![Command::replace_text(4, ""), Command::Pending(0)]
+    );
+}
+
+#[test]
+fn end_to_end_document() {
+    let dict = r#"{
+        "H-L": "hello",
+        "WORLD": "world",
+        "KW-BG": "{^,}",
+        "TP-PL": "{.}",
+        "HRAEF": {"cmds": [{ "Keys": [{"Special": "LeftArrow"}, ["Control"]] }]},
+        "RAOEUT": {"cmds": [{ "Keys": [{"Special": "RightArrow"}, ["Control"]] }]}
+    }"#
+    .to_string();
+    let mut translator = StandardTranslator::new(vec![dict], vec![], vec![], None, false).unwrap();
+    let mut buffer = BufferController::default();
+    let mut write = |strokes: &str| {
+        for s in strokes.split('/') {
+            let stroke = Stroke::new(s);
+            let commands = if stroke.is_undo() {
+                translator.undo()
+            } else {
+                translator.translate(stroke)
+            };
+            for command in commands {
+                buffer.dispatch(command);
+            }
+        }
+        buffer.render()
+    };
+
+    assert_eq!(write("H-L/KW-BG/WORLD/TP-PL"), " hello, world.|");
+    assert_eq!(write("*"), " hello, world|");
+    // keys move the cursor, so words are inserted in the middle (with the usual space before)
+    assert_eq!(write("HRAEF/H-L"), " hello,  hello|world");
+    assert_eq!(write("RAOEUT/TP-PL"), " hello,  helloworld.|");
+}
@@ -128,8 +128,8 @@ struct Cmd {
 /// This is the basic format: `{^}{#Shift_L(Alt_L(a))}{^}{-|}`
 /// Where the `{^}` in the beginning is optional and the ending `{^}` and `{-|}` are optional
 ///
-/// The keyboard shortcut in the middle follows the pattern `{#..}`. There must be only one
-/// shortcut key (no spaces).
+/// The keyboard shortcut in the middle follows the pattern `{#..}`. Several shortcuts separated by
+/// spaces are pressed one after another as a key sequence.
 ///
 /// The modifier keys are translated into the plojo format in the order they appear.
 ///
@@ -138,11 +138,11 @@ struct Cmd {
 fn convert_keyboard_shortcut(s: &str) -> Result<Value, ConversionError> {
     lazy_static! {
         static ref RE: Regex =
-            Regex::new(r#"^((?:\{\^\})?)\{#([^\} ]+)\}((?:\{\^\}(?:\{-\|\})?)?)$"#).unwrap();
+            Regex::new(r#"^((?:\{\^\})?)\{#([^\}]+)\}((?:\{\^\}(?:\{-\|\})?)?)$"#).unwrap();
     }
 
     if let Some(c) = RE.captures(s) {
-        let cmd = parse_key_combo(&c[2])?;
+        let cmd = parse_key_sequence(&c[2])?;
         let text_after = match &c[3] {
             "{^}{-|}" => Some(c[3].to_owned()),
             "{^}" => Some(c[3].to_owned()),
@@ -167,14 +167,81 @@ fn convert_keyboard_shortcut(s: &str) -> Result<Value, ConversionError> {
     }
 }
 
-/// Parses a single plover keyboard shortcut string into a plojo recognizable command
+/// Parses space separated plover keyboard shortcuts. A single shortcut is a `Keys` command and
+/// more than one is a `KeySequence`
+fn parse_key_sequence(s: &str) -> Result<Command, ConversionError> {
+    let mut keys = parse_keys(s)?;
+    match keys.len() {
+        0 => Err(ConversionError::InvalidKeyboardShortcut),
+        1 => {
+            let (key, modifiers) = keys.pop().unwrap();
+            Ok(Command::Keys(key, modifiers))
+        }
+        _ => Ok(Command::KeySequence(keys)),
+    }
+}
+
+/// Parses space separated plover keyboard shortcuts into keys and their modifiers. A modifier can
+/// be held down for several keys (`shift(a b)` presses shift+a and then shift+b)
+fn parse_keys(s: &str) -> Result<Vec<(Key, Vec<Modifier>)>, ConversionError> {
+    let mut keys = Vec::new();
+    for part in split_top_level(s)? {
+        if part.contains(char::is_whitespace) {
+            // the spaces are inside the parentheses of the modifier
+            let (modifier, inner) = part
+                .split_once('(')
+                .ok_or(ConversionError::InvalidKeyboardShortcut)?;
+            let inner = inner
+                .strip_suffix(')')
+                .ok_or(ConversionError::InvalidKeyboardShortcut)?;
+            let modifier = parse_plover_modifier(&modifier.to_lowercase())?;
+            for (key, mut modifiers) in parse_keys(inner)? {
+                modifiers.insert(0, modifier);
+                keys.push((key, modifiers));
+            }
+        } else {
+            keys.push(parse_key_combo(part)?);
+        }
+    }
+    Ok(keys)
+}
+
+/// Splits the shortcuts on the spaces that are not inside parentheses
+fn split_top_level(s: &str) -> Result<Vec<&str>, ConversionError> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Err(ConversionError::UnbalancedParens),
+            ')' => depth -= 1,
+            c if c.is_whitespace() && depth == 0 => {
+                if start < i {
+                    parts.push(&s[start..i]);
+                }
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err(ConversionError::UnbalancedParens);
+    }
+    if start < s.len() {
+        parts.push(&s[start..]);
+    }
+    Ok(parts)
+}
+
+/// Parses a single plover keyboard shortcut string into a key and the modifiers to hold down
 ///
 /// See plover documentation for details
 /// https://github.com/openstenoproject/plover/wiki/Dictionary-Format#keyboard-shortcuts
 ///
 /// This only accepts a single key + modifiers. Multiple keys do not work (there should not be
 /// spaces)
-fn parse_key_combo(s: &str) -> Result<Command, ConversionError> {
+fn parse_key_combo(s: &str) -> Result<(Key, Vec<Modifier>), ConversionError> {
     lazy_static! {
         static ref RE: Regex = Regex::new(r#"^((?:[a-z_]+\()*)([a-z0-9_]+)(\)*)$"#).unwrap();
     }
@@ -198,7 +265,7 @@ fn parse_key_combo(s: &str) -> Result<Command, ConversionError> {
 
         let key = parse_plover_key(&c[2])?;
 
-        Ok(Command::Keys(key, modifiers))
+        Ok((key, modifiers))
     } else {
         Err(ConversionError::InvalidKeyboardShortcut)
     }
@@ -249,6 +316,47 @@ fn parse_plover_key(k: &str) -> Result<Key, ConversionError> {
         "f10" => Ok(Key::Special(SpecialKey::F10)),
         "f11" => Ok(Key::Special(SpecialKey::F11)),
         "f12" => Ok(Key::Special(SpecialKey::F12)),
+        "f13" => Ok(Key::Special(SpecialKey::F13)),
+        "f14" => Ok(Key::Special(SpecialKey::F14)),
+        "f15" => Ok(Key::Special(SpecialKey::F15)),
+        "f16" => Ok(Key::Special(SpecialKey::F16)),
+        "f17" => Ok(Key::Special(SpecialKey::F17)),
+        "f18" => Ok(Key::Special(SpecialKey::F18)),
+        "f19" => Ok(Key::Special(SpecialKey::F19)),
+        "f20" => Ok(Key::Special(SpecialKey::F20)),
+        "f21" => Ok(Key::Special(SpecialKey::F21)),
+        "f22" => Ok(Key::Special(SpecialKey::F22)),
+        "f23" => Ok(Key::Special(SpecialKey::F23)),
+        "f24" => Ok(Key::Special(SpecialKey::F24)),
+        "insert" => Ok(Key::Special(SpecialKey::Insert)),
+        "print" => Ok(Key::Special(SpecialKey::PrintScreen)),
+        "pause" => Ok(Key::Special(SpecialKey::Pause)),
+        "scroll_lock" => Ok(Key::Special(SpecialKey::ScrollLock)),
+        "num_lock" => Ok(Key::Special(SpecialKey::NumLock)),
+        "menu" => Ok(Key::Special(SpecialKey::Menu)),
+        "kp_0" => Ok(Key::Special(SpecialKey::Numpad0)),
+        "kp_1" => Ok(Key::Special(SpecialKey::Numpad1)),
+        "kp_2" => Ok(Key::Special(SpecialKey::Numpad2)),
+        "kp_3" => Ok(Key::Special(SpecialKey::Numpad3)),
+        "kp_4" => Ok(Key::Special(SpecialKey::Numpad4)),
+        "kp_5" => Ok(Key::Special(SpecialKey::Numpad5)),
+        "kp_6" => Ok(Key::Special(SpecialKey::Numpad6)),
+        "kp_7" => Ok(Key::Special(SpecialKey::Numpad7)),
+        "kp_8" => Ok(Key::Special(SpecialKey::Numpad8)),
+        "kp_9" => Ok(Key::Special(SpecialKey::Numpad9)),
+        "kp_add" => Ok(Key::Special(SpecialKey::NumpadAdd)),
+        "kp_decimal" => Ok(Key::Special(SpecialKey::NumpadDecimal)),
+        "kp_divide" => Ok(Key::Special(SpecialKey::NumpadDivide)),
+        "kp_enter" => Ok(Key::Special(SpecialKey::NumpadEnter)),
+        "kp_multiply" => Ok(Key::Special(SpecialKey::NumpadMultiply)),
+        "kp_subtract" => Ok(Key::Special(SpecialKey::NumpadSubtract)),
+        "audioraisevolume" => Ok(Key::Special(SpecialKey::VolumeUp)),
+        "audiolowervolume" => Ok(Key::Special(SpecialKey::VolumeDown)),
+        "audiomute" => Ok(Key::Special(SpecialKey::VolumeMute)),
+        "audioplay" | "audiopause" => Ok(Key::Special(SpecialKey::MediaPlayPause)),
+        "audionext" => Ok(Key::Special(SpecialKey::MediaNextTrack)),
+        "audioprev" => Ok(Key::Special(SpecialKey::MediaPreviousTrack)),
+        "audiostop" => Ok(Key::Special(SpecialKey::MediaStop)),
         // copied from plover/key_combo.py
         "aacute" => Ok(Key::Layout('á')),
         "acircumflex" => Ok(Key::Layout('â')),
@@ -391,43 +499,86 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_convert_key_sequence() {
+        assert_eq!(
+            convert_keyboard_shortcut("{#Tab Tab}").unwrap(),
+            json!({
+                "cmds": [{
+                    "KeySequence": [[{ "Special": "Tab" }, []], [{ "Special": "Tab" }, []]]
+                }]
+            })
+        );
+        assert_eq!(
+            convert_keyboard_shortcut("{#super(a)  super(b)}{^}").unwrap(),
+            json!({
+                "cmds": [{
+                    "KeySequence": [[{ "Layout": "a" }, ["Meta"]], [{ "Layout": "b" }, ["Meta"]]]
+                }],
+                "text_after": "{^}",
+            })
+        );
+        // a modifier can be held down for several keys
+        assert_eq!(
+            convert_keyboard_shortcut("{#control(shift(a b) c) Tab}").unwrap(),
+            json!({
+                "cmds": [{
+                    "KeySequence": [
+                        [{ "Layout": "a" }, ["Control", "Shift"]],
+                        [{ "Layout": "b" }, ["Control", "Shift"]],
+                        [{ "Layout": "c" }, ["Control"]],
+                        [{ "Special": "Tab" }, []],
+                    ]
+                }]
+            })
+        );
+    }
+
     #[test]
     fn test_parse_key_combo() {
         assert_eq!(
             parse_key_combo("Control_L(Alt_L(Super_L(Left)))").unwrap(),
-            Command::Keys(
+            (
                 Key::Special(SpecialKey::LeftArrow),
                 vec![Modifier::Control, Modifier::Alt, Modifier::Meta]
             )
         );
         assert_eq!(
             parse_key_combo("option(a)").unwrap(),
-            Command::Keys(Key::Layout('a'), vec![Modifier::Option])
+            (Key::Layout('a'), vec![Modifier::Option])
         );
         assert_eq!(
             parse_key_combo("bAcKsPacE").unwrap(),
-            Command::Keys(Key::Special(SpecialKey::Backspace), vec![])
+            (Key::Special(SpecialKey::Backspace), vec![])
         );
-    }
-
-    #[test]
-    fn test_keyboard_shortcut_fails() {
         assert_eq!(
-            convert_keyboard_shortcut("{#Tab Tab}").unwrap_err(),
-            ConversionError::InvalidFormat
+            parse_key_combo("shift(KP_Add)").unwrap(),
+            (Key::Special(SpecialKey::NumpadAdd), vec![Modifier::Shift])
         );
         assert_eq!(
-            convert_keyboard_shortcut("{#super(a) super(b)}").unwrap_err(),
-            ConversionError::InvalidFormat
+            parse_key_combo("AudioRaiseVolume").unwrap(),
+            (Key::Special(SpecialKey::VolumeUp), vec![])
         );
         assert_eq!(
-            convert_keyboard_shortcut("{#shift_l(space space)}").unwrap_err(),
-            ConversionError::InvalidFormat
+            parse_key_combo("F24").unwrap(),
+            (Key::Special(SpecialKey::F24), vec![])
+        );
+    }
+
+    #[test]
+    fn test_keyboard_shortcut_fails() {
+        assert_eq!(
+            convert_keyboard_shortcut("{# }").unwrap_err(),
+            ConversionError::InvalidKeyboardShortcut
         );
         assert_eq!(
             convert_keyboard_shortcut("{#shift_l(alt_l(b)}").unwrap_err(),
             ConversionError::UnbalancedParens
         );
+        assert_eq!(
+            convert_keyboard_shortcut("{#shift_l(a b)c}").unwrap_err(),
+            ConversionError::InvalidKeyboardShortcut
+        );
     }
 
     #[test]